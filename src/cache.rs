@@ -0,0 +1,87 @@
+
+// --- IMPORTS ---
+
+use chain_ping::PingResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+// --- DATA STRUCTURES ---
+/// A single cached measurement and when it was taken (seconds since the Unix epoch)
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    stored_at: u64,
+    result: PingResult,
+}
+
+/// On-disk cache of recent results, used by `--cache-ttl`.
+///
+/// Lives in `$XDG_CACHE_HOME/chain-ping/results.json`, falling back to
+/// `~/.cache/chain-ping/results.json` and finally the system temp directory.
+pub struct ResultCache {
+    path: PathBuf,
+    entries: HashMap<String, CacheEntry>,
+}
+
+
+// --- CORE LOGIC ---
+impl ResultCache {
+    /// Loads the cache from disk. A missing or unreadable file just means an empty cache.
+    pub fn load() -> Self {
+        let path = cache_path();
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        ResultCache { path, entries }
+    }
+
+    /// Returns the result cached for this endpoint under the same measurement `settings`, if it is younger than `ttl`
+    pub fn get(&self, endpoint: &str, settings: &str, ttl: Duration) -> Option<PingResult> {
+        let entry = self.entries.get(&cache_key(endpoint, settings))?;
+        if now_secs().saturating_sub(entry.stored_at) > ttl.as_secs() {
+            return None;
+        }
+        let mut result = entry.result.clone();
+        result.from_cache = true;
+        Some(result)
+    }
+
+    pub fn insert(&mut self, result: &PingResult, settings: &str) {
+        let entry = CacheEntry { stored_at: now_secs(), result: result.clone() };
+        self.entries.insert(cache_key(&result.endpoint, settings), entry);
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string(&self.entries)?)
+    }
+}
+
+fn cache_path() -> PathBuf {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("chain-ping").join("results.json")
+}
+
+/// The settings are hashed rather than stored, since headers can carry API keys. FNV-1a, like
+/// `endpoint_id`, so the key stays the same across builds.
+fn cache_key(endpoint: &str, settings: &str) -> String {
+    let hash = settings.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x} {}", hash, endpoint)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
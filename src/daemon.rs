@@ -0,0 +1,185 @@
+
+// --- IMPORTS ---
+
+use crate::output::SortBy;
+use chain_ping::{build_client, ping_endpoint_with_client, PingConfig, PingResult};
+use futures::future::{join_all, BoxFuture};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests larger than this are rejected rather than buffered
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Longest request or header line read, and most header lines, before the request is rejected
+const MAX_LINE_BYTES: u64 = 8 * 1024;
+const MAX_HEADER_LINES: usize = 100;
+
+/// The command line's markup for one result (tags, labels and per-endpoint checks), given the
+/// request's labels by URL
+pub type MarkUp = Arc<dyn Fn(PingResult, Arc<BTreeMap<String, String>>) -> BoxFuture<'static, PingResult> + Send + Sync>;
+
+
+// --- DATA STRUCTURES ---
+/// Body of a `POST /ping` request
+#[derive(Deserialize)]
+struct PingRequest {
+    endpoints: Vec<String>,
+    /// Overrides `--pings` for this request
+    pings: Option<usize>,
+}
+
+struct Response {
+    status: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn error(status: &'static str, message: &str) -> Self {
+        Response { status, body: serde_json::json!({ "error": message }).to_string() }
+    }
+}
+
+
+// --- SERVER ---
+/// Serves `POST /ping` on `addr` until the process is stopped.
+///
+/// One HTTP client is shared by every request, so connections to endpoints that were pinged
+/// before are already warm. The response is the same JSON array `--format json` prints, with each
+/// result passed through `mark_up` first.
+pub async fn serve(addr: SocketAddr, config: PingConfig, quiet: bool, mark_up: MarkUp) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    let client = build_client(&config).map_err(std::io::Error::other)?;
+    let config = Arc::new(config);
+
+    if !quiet {
+        eprintln!("Listening on http://{} (POST /ping)", listener.local_addr()?);
+    }
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let client = client.clone();
+        let config = config.clone();
+        let mark_up = mark_up.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &client, &config, &mark_up).await {
+                eprintln!("Warning: Daemon connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream, client: &reqwest::Client, config: &PingConfig, mark_up: &MarkUp) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let request_line = read_capped_line(&mut reader).await?;
+    let (method, path) = match &request_line {
+        Some(line) => {
+            let mut parts = line.split_whitespace();
+            (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+        }
+        None => ("", ""),
+    };
+
+    // Only Content-Length matters to us; everything else in the header block is skipped
+    let mut content_length = 0;
+    let mut too_large = request_line.is_none();
+    let mut header_lines = 0;
+    while !too_large {
+        let Some(line) = read_capped_line(&mut reader).await? else {
+            too_large = true;
+            break;
+        };
+        if line.trim().is_empty() {
+            break;
+        }
+        header_lines += 1;
+        if header_lines > MAX_HEADER_LINES {
+            too_large = true;
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    let response = if too_large {
+        Response::error("431 Request Header Fields Too Large", "Request line or headers too large")
+    } else if path != "/ping" {
+        Response::error("404 Not Found", "Not found. Use POST /ping")
+    } else if method != "POST" {
+        Response::error("405 Method Not Allowed", "Use POST /ping")
+    } else if content_length > MAX_BODY_BYTES {
+        Response::error("413 Payload Too Large", "Request body too large")
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await?;
+        handle_ping(&body, client, config, mark_up).await
+    };
+
+    let mut stream = reader.into_inner();
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.body.len(),
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Reads one line, or `None` if it runs past `MAX_LINE_BYTES` without ending. An empty line
+/// means the connection closed.
+async fn read_capped_line(reader: &mut BufReader<TcpStream>) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader.take(MAX_LINE_BYTES).read_line(&mut line).await?;
+    if read as u64 == MAX_LINE_BYTES && !line.ends_with('\n') {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+async fn handle_ping(body: &[u8], client: &reqwest::Client, config: &PingConfig, mark_up: &MarkUp) -> Response {
+    let request: PingRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return Response::error("400 Bad Request", &format!("Invalid request body: {}", e)),
+    };
+    if request.endpoints.is_empty() {
+        return Response::error("400 Bad Request", "At least one endpoint URL is required");
+    }
+    if request.pings == Some(0) {
+        return Response::error("400 Bad Request", "pings must be at least 1");
+    }
+
+    // `label=url` endpoints work as on the command line
+    let mut labels = BTreeMap::new();
+    let urls: Vec<String> = request
+        .endpoints
+        .iter()
+        .map(|endpoint| {
+            let (label, url) = crate::split_label(endpoint);
+            if let Some(label) = label {
+                labels.insert(url.to_string(), label.to_string());
+            }
+            url.to_string()
+        })
+        .collect();
+    let labels = Arc::new(labels);
+
+    let config = PingConfig { count: request.pings.unwrap_or(config.count), ..config.clone() };
+    let ping_futures = urls.iter().map(|endpoint| async {
+        mark_up(ping_endpoint_with_client(client, endpoint, &config).await, labels.clone()).await
+    });
+    let mut results = join_all(ping_futures).await;
+    crate::sort_results(&mut results, SortBy::Latency, false, &urls);
+
+    match serde_json::to_string(&results) {
+        Ok(body) => Response { status: "200 OK", body },
+        Err(_) => Response::error("500 Internal Server Error", "Failed to serialize results to JSON"),
+    }
+}
@@ -0,0 +1,386 @@
+
+// --- IMPORTS ---
+
+use chain_ping::{decode_block_number, PingResult, PingStatus, SyncStatus};
+use serde::Serialize;
+use std::str::FromStr;
+
+
+// --- DATA STRUCTURES ---
+/// How many healthy endpoints reported a given head height
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HeightCount {
+    pub height: u64,
+    pub endpoints: usize,
+}
+
+/// Which endpoint results make the run exit unhealthy, set by `--fail-on`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailOn {
+    /// Anything short of every ping succeeding
+    Partial,
+    /// Only endpoints where every ping failed
+    Failure,
+    Never,
+}
+
+impl FromStr for FailOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "partial" => Ok(FailOn::Partial),
+            "failure" => Ok(FailOn::Failure),
+            "never" => Ok(FailOn::Never),
+            _ => Err(format!("unknown fail-on '{}'. Use 'partial', 'failure' or 'never'", s)),
+        }
+    }
+}
+
+
+// --- FLEET CHECKS ---
+/// Healthy endpoints' head heights, for comparing them across the fleet
+fn healthy_heights(results: &[PingResult]) -> Vec<(&str, u64)> {
+    results
+        .iter()
+        .filter(|r| r.status != PingStatus::Failure)
+        .filter_map(|r| Some((r.endpoint.as_str(), decode_block_number(r.result.as_deref()?)?)))
+        .collect()
+}
+
+/// Tallies healthy endpoints per observed head height, highest first
+pub fn block_distribution(results: &[PingResult]) -> Vec<HeightCount> {
+    let mut counts = std::collections::BTreeMap::new();
+    for (_, height) in healthy_heights(results) {
+        *counts.entry(height).or_insert(0) += 1;
+    }
+    counts.into_iter().rev().map(|(height, endpoints)| HeightCount { height, endpoints }).collect()
+}
+
+/// One-line summary such as `Block heights: 19423871: 6 endpoints, 19423870: 1 endpoint`
+pub fn format_block_distribution(distribution: &[HeightCount]) -> String {
+    let entries: Vec<String> = distribution
+        .iter()
+        .map(|c| format!("{}: {} {}", c.height, c.endpoints, if c.endpoints == 1 { "endpoint" } else { "endpoints" }))
+        .collect();
+    format!("Block heights: {}", entries.join(", "))
+}
+
+/// Checks that the healthy endpoints' head heights are within `max_divergence` blocks of each other.
+///
+/// Failed endpoints and endpoints without a decodable block number are left out of the comparison.
+/// On failure the message names the leaders (at the highest height) and the laggards.
+pub fn check_block_divergence(results: &[PingResult], max_divergence: u64) -> Result<(), String> {
+    let heights = healthy_heights(results);
+
+    let (Some(highest), Some(lowest)) = (
+        heights.iter().map(|(_, h)| *h).max(),
+        heights.iter().map(|(_, h)| *h).min(),
+    ) else {
+        return Ok(());
+    };
+
+    if highest - lowest <= max_divergence {
+        return Ok(());
+    }
+
+    let leaders: Vec<&str> = heights.iter().filter(|(_, h)| *h == highest).map(|(e, _)| *e).collect();
+    let laggards: Vec<String> = heights
+        .iter()
+        .filter(|(_, h)| highest - h > max_divergence)
+        .map(|(e, h)| format!("{} ({} behind)", e, highest - h))
+        .collect();
+
+    Err(format!(
+        "Block heights diverge by {} blocks (max {}). Leaders at {}: {}. Laggards: {}",
+        highest - lowest,
+        max_divergence,
+        highest,
+        leaders.join(", "),
+        laggards.join(", "),
+    ))
+}
+
+/// Records how far each healthy endpoint trails the highest head, marking those over `max_lag` as lagging.
+///
+/// Failed endpoints and endpoints without a decodable block number get no lag.
+pub fn flag_lag(results: &mut [PingResult], max_lag: u64) {
+    let Some(highest) = healthy_heights(results).iter().map(|(_, h)| *h).max() else {
+        return;
+    };
+    for result in results.iter_mut().filter(|r| r.status != PingStatus::Failure) {
+        let Some(height) = result.result.as_deref().and_then(decode_block_number) else {
+            continue;
+        };
+        let behind = highest - height;
+        result.blocks_behind = Some(behind);
+        result.lagging = behind > max_lag;
+    }
+}
+
+/// Marks endpoints whose head is below `min_height` as suspect, e.g. a devnet or a broken node.
+///
+/// With `fail`, those endpoints are also downgraded to failures.
+pub fn flag_below_min_height(results: &mut [PingResult], min_height: u64, fail: bool) {
+    for result in results.iter_mut() {
+        let Some(height) = result.result.as_deref().and_then(decode_block_number) else {
+            continue;
+        };
+        if height >= min_height {
+            continue;
+        }
+        result.below_min_height = true;
+        if fail {
+            result.status = PingStatus::Failure;
+            let message = format!("Block {} below --min-block-height {}", height, min_height);
+            result.distinct_errors.push(message.clone());
+            result.error_message = Some(message);
+        }
+    }
+}
+
+/// Downgrades a fully successful endpoint to partial when `--check-sync` found it still syncing, since
+/// its answers may be stale. Endpoints that already failed some pings are left as they are.
+pub fn downgrade_syncing(results: &mut [PingResult]) {
+    for result in results.iter_mut() {
+        if result.sync_status == Some(SyncStatus::Syncing) && result.status == PingStatus::Success {
+            result.status = PingStatus::PartialSuccess;
+            let message = "Node is still syncing".to_string();
+            result.distinct_errors.push(message.clone());
+            result.error_message = Some(message);
+        }
+    }
+}
+
+/// Fails every endpoint that answered but isn't on one of the `allowed` chains, including
+/// those whose chain id couldn't be fetched. The reported `chain_id` is left as it is.
+pub fn enforce_allowed_chain_ids(results: &mut [PingResult], allowed: &[u64]) {
+    let allowed_list = allowed.iter().map(u64::to_string).collect::<Vec<_>>().join(", ");
+    for result in results.iter_mut().filter(|r| r.status != PingStatus::Failure) {
+        let message = match result.chain_id {
+            Some(chain_id) if allowed.contains(&chain_id) => continue,
+            Some(chain_id) if allowed.len() == 1 => format!("Chain {}, expected {}", chain_id, allowed_list),
+            Some(chain_id) => format!("Chain {} is not one of the allowed chains ({})", chain_id, allowed_list),
+            None => format!("Chain id unknown, so it can't be checked against the allowed chains ({})", allowed_list),
+        };
+        result.status = PingStatus::Failure;
+        result.distinct_errors.push(message.clone());
+        result.error_message = Some(message);
+    }
+}
+
+/// Checks that every healthy endpoint with a known chain id is on the same chain.
+///
+/// The most common id is taken as the fleet's chain, and the message names the endpoints on any other.
+pub fn check_same_chain(results: &[PingResult]) -> Result<(), String> {
+    let chains: Vec<(&str, u64)> = results
+        .iter()
+        .filter(|r| r.status != PingStatus::Failure)
+        .filter_map(|r| Some((r.endpoint.as_str(), r.chain_id?)))
+        .collect();
+
+    let mut counts = std::collections::BTreeMap::new();
+    for (_, chain_id) in &chains {
+        *counts.entry(*chain_id).or_insert(0) += 1;
+    }
+    if counts.len() <= 1 {
+        return Ok(());
+    }
+    // Ties go to the lower id, so the message doesn't change between runs
+    let (majority, majority_count) = counts
+        .iter()
+        .max_by(|(id_a, count_a), (id_b, count_b)| count_a.cmp(count_b).then(id_b.cmp(id_a)))
+        .map(|(id, count)| (*id, *count))
+        .expect("at least two chain ids were counted");
+
+    let odd_ones: Vec<String> = chains
+        .iter()
+        .filter(|(_, chain_id)| *chain_id != majority)
+        .map(|(endpoint, chain_id)| format!("{} (chain {})", endpoint, chain_id))
+        .collect();
+
+    Err(format!(
+        "Endpoints disagree on the chain. Most are on chain {} ({} {}). Odd ones: {}",
+        majority,
+        majority_count,
+        if majority_count == 1 { "endpoint" } else { "endpoints" },
+        odd_ones.join(", "),
+    ))
+}
+
+/// Checks that no endpoint's status is at or past the `fail_on` threshold, naming the ones that are
+pub fn check_health(results: &[PingResult], fail_on: FailOn) -> Result<(), String> {
+    let unhealthy: Vec<String> = results
+        .iter()
+        .filter(|r| match fail_on {
+            FailOn::Partial => r.status != PingStatus::Success,
+            FailOn::Failure => r.status == PingStatus::Failure,
+            FailOn::Never => false,
+        })
+        .map(|r| match r.status {
+            PingStatus::PartialSuccess => format!("{} (partial)", r.endpoint),
+            _ => r.endpoint.clone(),
+        })
+        .collect();
+
+    if unhealthy.is_empty() {
+        return Ok(());
+    }
+    let noun = if results.len() == 1 { "endpoint" } else { "endpoints" };
+    Err(format!("{} of {} {} unhealthy: {}", unhealthy.len(), results.len(), noun, unhealthy.join(", ")))
+}
+
+// --- TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(endpoint: &str, status: PingStatus, block: Option<&str>) -> PingResult {
+        PingResult {
+            endpoint: endpoint.to_string(),
+            status,
+            result: block.map(str::to_string),
+            ..PingResult::default()
+        }
+    }
+
+    #[test]
+    fn test_check_health_thresholds() {
+        let results = vec![
+            result("a", PingStatus::Success, None),
+            result("b", PingStatus::PartialSuccess, None),
+            result("c", PingStatus::Failure, None),
+        ];
+        assert_eq!(check_health(&results, FailOn::Failure).unwrap_err(), "1 of 3 endpoints unhealthy: c");
+        assert_eq!(check_health(&results, FailOn::Partial).unwrap_err(), "2 of 3 endpoints unhealthy: b (partial), c");
+        assert!(check_health(&results, FailOn::Never).is_ok());
+        assert!(check_health(&results[..2], FailOn::Failure).is_ok());
+    }
+
+    #[test]
+    fn test_block_divergence_within_threshold() {
+        let results = vec![
+            result("a", PingStatus::Success, Some("\"0x64\"")), // 100
+            result("b", PingStatus::Success, Some("\"0x62\"")), // 98
+        ];
+        assert!(check_block_divergence(&results, 2).is_ok());
+    }
+
+    #[test]
+    fn test_block_divergence_names_laggards() {
+        let results = vec![
+            result("https://a.example", PingStatus::Success, Some("\"0x64\"")), // 100
+            result("https://b.example", PingStatus::PartialSuccess, Some("\"0x32\"")), // 50
+            result("https://c.example", PingStatus::Failure, None),
+        ];
+        let message = check_block_divergence(&results, 10).unwrap_err();
+        assert!(message.contains("Leaders at 100: https://a.example"));
+        assert!(message.contains("https://b.example (50 behind)"));
+        assert!(!message.contains("https://c.example"));
+    }
+
+    #[test]
+    fn test_same_chain_names_odd_ones() {
+        let with_chain = |endpoint: &str, status: PingStatus, chain_id: Option<u64>| PingResult {
+            chain_id,
+            ..result(endpoint, status, None)
+        };
+        let results = vec![
+            with_chain("https://a.example", PingStatus::Success, Some(1)),
+            with_chain("https://b.example", PingStatus::Success, Some(1)),
+            with_chain("https://sepolia.example", PingStatus::Success, Some(11155111)),
+            with_chain("https://down.example", PingStatus::Failure, Some(5)),
+        ];
+        let message = check_same_chain(&results).unwrap_err();
+        assert!(message.contains("Most are on chain 1 (2 endpoints)"));
+        assert!(message.contains("Odd ones: https://sepolia.example (chain 11155111)"));
+        assert!(!message.contains("down.example"));
+
+        assert!(check_same_chain(&results[..2]).is_ok());
+    }
+
+    #[test]
+    fn test_allowed_chain_ids() {
+        let with_chain = |endpoint: &str, status: PingStatus, chain_id: Option<u64>| PingResult {
+            chain_id,
+            ..result(endpoint, status, None)
+        };
+        let mut results = vec![
+            with_chain("mainnet", PingStatus::Success, Some(1)),
+            with_chain("base", PingStatus::PartialSuccess, Some(8453)),
+            with_chain("sepolia", PingStatus::Success, Some(11155111)),
+            with_chain("silent", PingStatus::Success, None),
+        ];
+        enforce_allowed_chain_ids(&mut results, &[1, 8453]);
+        assert_eq!(results[0].status, PingStatus::Success);
+        assert_eq!(results[1].status, PingStatus::PartialSuccess);
+        assert_eq!(results[2].status, PingStatus::Failure);
+        assert_eq!(results[2].chain_id, Some(11155111));
+        assert_eq!(results[2].error_message.as_deref(), Some("Chain 11155111 is not one of the allowed chains (1, 8453)"));
+        assert_eq!(results[3].status, PingStatus::Failure);
+
+        let mut results = vec![with_chain("sepolia", PingStatus::Success, Some(11155111))];
+        enforce_allowed_chain_ids(&mut results, &[1]);
+        assert_eq!(results[0].error_message.as_deref(), Some("Chain 11155111, expected 1"));
+    }
+
+    #[test]
+    fn test_flag_lag() {
+        let mut results = vec![
+            result("leader", PingStatus::Success, Some("\"0x64\"")), // 100
+            result("close", PingStatus::Success, Some("\"0x62\"")), // 98
+            result("behind", PingStatus::PartialSuccess, Some("\"0x50\"")), // 80
+            result("down", PingStatus::Failure, Some("\"0x70\"")),
+        ];
+        flag_lag(&mut results, 2);
+        let lags: Vec<(Option<u64>, bool)> = results.iter().map(|r| (r.blocks_behind, r.lagging)).collect();
+        assert_eq!(lags, vec![(Some(0), false), (Some(2), false), (Some(20), true), (None, false)]);
+    }
+
+    #[test]
+    fn test_flag_below_min_height() {
+        let mut results = vec![
+            result("devnet", PingStatus::Success, Some("\"0x5\"")),
+            result("mainnet", PingStatus::Success, Some("\"0x1298c4f\"")),
+        ];
+        flag_below_min_height(&mut results, 1_000_000, true);
+        assert!(results[0].below_min_height);
+        assert_eq!(results[0].status, PingStatus::Failure);
+        assert!(!results[1].below_min_height);
+        assert_eq!(results[1].status, PingStatus::Success);
+    }
+
+    #[test]
+    fn test_downgrade_syncing() {
+        let with_sync = |endpoint: &str, status: PingStatus, sync_status: Option<SyncStatus>| PingResult {
+            sync_status,
+            ..result(endpoint, status, Some("\"0x64\""))
+        };
+        let mut results = vec![
+            with_sync("syncing", PingStatus::Success, Some(SyncStatus::Syncing)),
+            with_sync("synced", PingStatus::Success, Some(SyncStatus::Synced)),
+            with_sync("unknown", PingStatus::Success, None),
+            with_sync("down", PingStatus::Failure, Some(SyncStatus::Syncing)),
+        ];
+        downgrade_syncing(&mut results);
+        let statuses: Vec<PingStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![PingStatus::PartialSuccess, PingStatus::Success, PingStatus::Success, PingStatus::Failure]);
+        assert_eq!(results[0].error_message.as_deref(), Some("Node is still syncing"));
+    }
+
+    #[test]
+    fn test_block_distribution_sorted_descending() {
+        let results = vec![
+            result("a", PingStatus::Success, Some("\"0x63\"")), // 99
+            result("b", PingStatus::Success, Some("\"0x64\"")), // 100
+            result("c", PingStatus::Success, Some("\"0x64\"")), // 100
+            result("d", PingStatus::Failure, None),
+        ];
+        let distribution = block_distribution(&results);
+        assert_eq!(
+            distribution,
+            vec![HeightCount { height: 100, endpoints: 2 }, HeightCount { height: 99, endpoints: 1 }]
+        );
+        assert_eq!(format_block_distribution(&distribution), "Block heights: 100: 2 endpoints, 99: 1 endpoint");
+    }
+}
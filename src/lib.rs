@@ -1,54 +1,602 @@
 
 // --- IMPORTS ---
 
-use serde::Serialize;
-use std::time::{Duration, Instant};
+pub mod predicate;
+mod ws;
+
+use predicate::{Predicate, PredicateVars};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 
+/// The JSON-RPC method used to probe each endpoint
+pub const PROBE_METHOD: &str = "eth_blockNumber";
+
+/// Sent as the `User-Agent` unless `PingConfig::user_agent` says otherwise
+pub const DEFAULT_USER_AGENT: &str = concat!("chain-ping/", env!("CARGO_PKG_VERSION"));
+
+
 // --- DATA STRUCTURES ---
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 /// The final, aggregated result of pinging an endpoint multiple times
 pub struct PingResult {
     pub endpoint: String,
+    /// Short name given on the command line as `label=url`, shown instead of the URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Stable hash of the endpoint's host and path with API keys left out, see [`endpoint_id`]
+    #[serde(default)]
+    pub endpoint_id: String,
     pub avg_latency_ms: Option<u128>,
+    pub avg_latency_ms_exact: Option<f64>,
     pub min_latency_ms: Option<u128>,
+    pub min_latency_ms_exact: Option<f64>,
     pub max_latency_ms: Option<u128>,
-    pub block_number: Option<String>,
+    pub max_latency_ms_exact: Option<f64>,
+    /// Middle latency of the successful pings (the mean of the two middle ones for an even count)
+    #[serde(default)]
+    pub median_latency_ms: Option<u128>,
+    #[serde(default)]
+    pub median_latency_ms_exact: Option<f64>,
+    /// Jitter: the population standard deviation of the successful pings' latencies
+    #[serde(default)]
+    pub stddev_latency_ms: Option<u128>,
+    #[serde(default)]
+    pub stddev_latency_ms_exact: Option<f64>,
+    /// 95% confidence interval for the mean latency, `None` with fewer than 5 successful pings
+    #[serde(default)]
+    pub avg_latency_ci_ms: Option<(u128, u128)>,
+    /// From sending the endpoint's first ping to receiving its first successful response,
+    /// so failed pings (and pauses) before it count
+    #[serde(default)]
+    pub time_to_first_success_ms: Option<u128>,
+    /// The raw JSON `result` of the last successful ping (a block number for the default method)
+    #[serde(alias = "block_number")]
+    pub result: Option<String>,
+    /// `result` decoded from hex, for the default method only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_height: Option<u64>,
     pub ping_count: usize,
     pub success_count: usize,
     pub status: PingStatus,
     pub error_message: Option<String>,
+    /// Category of `error_message`, for library and JSON consumers aggregating failures
+    #[serde(default)]
+    pub error_kind: Option<ErrorKind>,
+    /// How many failed pings there were of each `ErrorKind`, keyed by its name such as `timeout` or `http 429`
+    #[serde(default)]
+    pub error_counts: BTreeMap<String, usize>,
+    /// Every different error message seen across the run, in the order they first appeared
+    #[serde(default)]
+    pub distinct_errors: Vec<String>,
+    /// User-supplied `--tag key=value` metadata, echoed onto every result
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// True when this result was served from the `--cache-ttl` cache instead of the network
+    #[serde(default)]
+    pub from_cache: bool,
+    /// True when the run was cancelled before every ping finished; the counts cover the ones that did
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+    /// Set by `--min-block-height` when the reported head is suspiciously low
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub below_min_height: bool,
+    /// How many blocks this endpoint's head trails the highest healthy head, set by `--max-lag`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks_behind: Option<u64>,
+    /// Set by `--max-lag` when `blocks_behind` is over the limit
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lagging: bool,
+    /// The endpoint's `eth_chainId`, fetched by `--same-chain` or `--allowed-chain-ids`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    /// The endpoint's `net_peerCount`, fetched by `--check-peers`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_count: Option<u64>,
+    /// Whether the node reports itself synced, fetched by `--check-sync`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_status: Option<SyncStatus>,
+    /// The endpoint's `eth_gasPrice` in wei, fetched by `--check-gas`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_price_wei: Option<u128>,
+    /// Latency over a fresh connection, set by `--cold-warm`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_latency_ms: Option<u128>,
+    /// Latency reusing the warmed-up connection, set by `--cold-warm`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_latency_ms: Option<u128>,
+    /// Connection setup overhead: cold minus warm latency. Can be slightly negative from noise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handshake_ms: Option<i128>,
+    /// `Content-Type` of the last successful response
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Average time spent parsing the JSON body, in fractional milliseconds
+    #[serde(default)]
+    pub avg_parse_ms: Option<f64>,
+    /// Average size of the successful responses' bodies, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_response_bytes: Option<usize>,
+    /// Time to set up a connection, in fractional milliseconds. For HTTP endpoints this is the average
+    /// DNS lookup and TCP connect of a separate probe connection opened before each successful ping;
+    /// for WebSocket endpoints it's the one real connection, including the upgrade.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_ms: Option<f64>,
+    /// Calls per ping with `PingConfig::batch`. The latency stats are then per batch round trip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<usize>,
+    /// Average batch latency divided by `batch_size`, in fractional milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_call_latency_ms: Option<f64>,
+    /// Every individual ping in the order it was sent. Not included in JSON output.
+    #[serde(skip)]
+    pub attempts: Vec<PingAttempt>,
+    /// Body of the fastest successful ping, kept with `PingConfig::capture_extremes`
+    #[serde(skip)]
+    pub fastest_response: Option<CapturedBody>,
+    /// Body of the slowest successful ping, kept with `PingConfig::capture_extremes`
+    #[serde(skip)]
+    pub slowest_response: Option<CapturedBody>,
+}
+
+/// A raw response body together with how long that ping took
+#[derive(Debug, Clone)]
+pub struct CapturedBody {
+    pub latency_ms: f64,
+    pub body: Vec<u8>,
+}
+
+/// The outcome of one individual ping
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingAttempt {
+    /// Zero-based position of this ping within the run
+    pub index: usize,
+    /// When the request was sent, in milliseconds since the Unix epoch
+    pub timestamp_ms: u128,
+    /// Round-trip latency in fractional milliseconds, only known for successful pings
+    pub latency_ms: Option<f64>,
+    pub success: bool,
+    /// The JSON-RPC `result` of a successful ping
+    pub result: Option<String>,
+    /// Why the ping failed, in the same words as `PingResult::error_message`
+    pub error: Option<String>,
+}
+
+/// A `PingResult` with its individual attempts split out, for callers computing their own statistics
+#[derive(Debug, Clone)]
+pub struct DetailedPingResult {
+    /// The aggregated result, with `attempts` left empty
+    pub summary: PingResult,
+    /// Every individual ping in the order it was sent
+    pub attempts: Vec<PingAttempt>,
+}
+
+impl From<DetailedPingResult> for PingResult {
+    fn from(detailed: DetailedPingResult) -> Self {
+        PingResult { attempts: detailed.attempts, ..detailed.summary }
+    }
+}
+
+/// What kind of failure an error was, for grouping failures without parsing `error_message`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum ErrorKind {
+    /// Waiting for or reading the response took longer than the timeout
+    Timeout,
+    /// Setting up the connection took longer than the connect timeout
+    ConnectTimeout,
+    /// DNS, TCP or TLS failure before a request could be sent
+    ConnectionFailed,
+    /// The endpoint answered with a non-success HTTP status
+    HttpStatus(u16),
+    /// The endpoint answered with a JSON-RPC error, or without a `result`
+    JsonRpc,
+    /// The HTTP client couldn't be set up, e.g. from an invalid proxy or header
+    ClientBuild,
+    /// Anything else: invalid JSON, wrong Content-Type, oversized responses, `--success-if` and so on
+    Other,
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::Timeout => f.write_str("timeout"),
+            ErrorKind::ConnectTimeout => f.write_str("connect timeout"),
+            ErrorKind::ConnectionFailed => f.write_str("connection failed"),
+            ErrorKind::HttpStatus(status) => write!(f, "http {}", status),
+            ErrorKind::JsonRpc => f.write_str("json-rpc"),
+            ErrorKind::ClientBuild => f.write_str("client build"),
+            ErrorKind::Other => f.write_str("other"),
+        }
+    }
 }
 
 /// A simple summary of the outcome
-#[derive(Debug, Serialize, PartialEq, Copy, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone, Default)]
 pub enum PingStatus {
     Success,
     PartialSuccess,
+    #[default]
     Failure,
 } 
 
+/// What a node's `eth_syncing` answer says about it
+#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStatus {
+    Synced,
+    Syncing,
+}
+
+/// How exact (fractional) latencies are rounded into the whole-millisecond `*_latency_ms` fields
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum Rounding {
+    #[default]
+    Nearest,
+    Floor,
+    Ceil,
+}
+
+impl Rounding {
+    fn apply(self, value: f64) -> u128 {
+        self.round(value) as u128
+    }
+
+    /// Like `apply`, for differences that can come out negative
+    fn apply_signed(self, value: f64) -> i128 {
+        self.round(value) as i128
+    }
+
+    fn round(self, value: f64) -> f64 {
+        match self {
+            Rounding::Nearest => value.round(),
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+        }
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Rounding::Nearest),
+            "floor" => Ok(Rounding::Floor),
+            "ceil" => Ok(Rounding::Ceil),
+            _ => Err(format!("unknown rounding '{}'. Use 'nearest', 'floor' or 'ceil'", s)),
+        }
+    }
+}
+
+/// Settings shared by every ping against an endpoint
+#[derive(Debug, Clone)]
+pub struct PingConfig {
+    /// JSON-RPC method every ping calls
+    pub method: String,
+    /// Params sent with `method`, always a JSON array
+    pub params: serde_json::Value,
+    /// Number of pings to perform
+    pub count: usize,
+    /// Timeout for each individual request in seconds, connecting included
+    pub timeout_secs: u64,
+    pub rounding: Rounding,
+    /// Sent as the `Origin` and `Referer` headers, for endpoints gated to a browser origin
+    pub origin: Option<String>,
+    /// Fail pings whose response isn't served as JSON, even if the body parses
+    pub strict_content_type: bool,
+    /// Called as each endpoint starts, completes a ping, and finishes
+    pub on_event: Option<EventCallback>,
+    /// When set, decides whether each parsed response counts as a success
+    pub success_if: Option<Predicate>,
+    /// Stop reading a response body once it grows past this many bytes
+    pub max_response_bytes: Option<u64>,
+    /// Source address for outgoing connections, to pick the network interface on multi-homed hosts
+    pub local_address: Option<IpAddr>,
+    /// Minimum time between the starts of consecutive pings to one endpoint
+    pub ping_spacing: Option<Duration>,
+    /// Pause between one ping finishing and the next starting. Zero pings back to back.
+    pub interval: Duration,
+    /// Keep the raw bodies of each endpoint's fastest and slowest successful pings
+    pub capture_extremes: bool,
+    /// How many of an endpoint's pings may be in flight at once. 1 sends them one after another.
+    pub concurrency: usize,
+    /// Fail a share of successful pings on purpose, for testing alerts. Not real packet loss.
+    pub simulate_loss: Option<SimulatedLoss>,
+    /// Resend a ping up to this many times when the request itself fails; only the last try counts
+    pub retries: usize,
+    /// Wait before the first retry, doubling for each one after it up to `MAX_RETRY_BACKOFF`
+    pub retry_backoff: Duration,
+    /// Extra `(name, value)` headers sent with every request, e.g. API keys. For a repeated name the last value wins.
+    pub headers: Vec<(String, String)>,
+    /// HTTP or SOCKS5 proxy for every request. When `None`, the `HTTPS_PROXY`/`HTTP_PROXY` environment is used.
+    pub proxy: Option<String>,
+    /// Skip TLS certificate verification, for nodes with self-signed certificates
+    pub accept_invalid_certs: bool,
+    /// JSON-RPC `id` of an endpoint's first ping, counting up from there so each request is distinct
+    pub id_start: u64,
+    /// Uncounted pings sent before the measured ones, so connection setup doesn't skew the stats.
+    /// The cold/warm and alternating modes measure connection setup on purpose and ignore it.
+    pub warmup: usize,
+    /// Send each ping as a JSON-RPC batch of this many identical calls, with ids counting up from
+    /// the ping's. HTTP endpoints only; WebSocket pings are never batched.
+    pub batch: Option<usize>,
+    /// Separate, usually shorter, limit on setting up a connection. `None` leaves it to `timeout_secs`.
+    pub connect_timeout_secs: Option<u64>,
+    /// Stops the run early when cancelled, e.g. on Ctrl-C
+    pub cancel: Option<Cancellation>,
+    /// Sent as the `User-Agent` header. An entry for it in `headers` takes precedence.
+    pub user_agent: String,
+    /// Time one throwaway connection to each HTTP endpoint for `connect_ms`. Off when nothing shows it.
+    pub probe_connect: bool,
+}
+
+/// Longest wait between retries, however many there are
+pub const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Longest the `connect_ms` probe waits for a connection, whatever the connect timeout
+const MAX_CONNECT_PROBE: Duration = Duration::from_secs(2);
+
+/// Deterministically fails a percentage of otherwise successful pings, set by `--simulate-loss`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulatedLoss {
+    /// Share of pings to fail, from 0 to 100
+    pub percent: f64,
+    /// The same seed fails the same pings of the same endpoints on every run
+    pub seed: u64,
+}
+
+impl SimulatedLoss {
+    /// Each draw depends only on the seed, endpoint and ping index, so concurrency and
+    /// endpoint order don't change which pings are dropped
+    fn drops(&self, url: &str, index: usize) -> bool {
+        let stream = url.bytes().fold(self.seed, |state, byte| splitmix64(state ^ byte as u64));
+        let draw = splitmix64(stream ^ index as u64);
+        // The top 53 bits as a uniform fraction in [0, 1)
+        (draw >> 11) as f64 / (1_u64 << 53) as f64 * 100.0 < self.percent
+    }
+}
+
+/// A small, well-mixed step function, so seeded runs need no RNG dependency
+fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl PingConfig {
+    /// The JSON-RPC `id` for ping `index`. Retries of a ping resend the same id.
+    fn request_id(&self, index: usize) -> u64 {
+        self.id_start.wrapping_add(index as u64)
+    }
+
+    /// How long setting up a connection may take
+    fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs.unwrap_or(self.timeout_secs))
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(Cancellation::is_cancelled)
+    }
+
+    /// Runs `work` to completion, or gives `None` as soon as the run is cancelled
+    async fn unless_cancelled<T>(&self, work: impl std::future::Future<Output = T>) -> Option<T> {
+        let Some(cancel) = &self.cancel else {
+            return Some(work.await);
+        };
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => None,
+            output = work => Some(output),
+        }
+    }
+
+    /// Builds and delivers an event, but only if someone is listening
+    fn emit(&self, event: impl FnOnce() -> PingEvent) {
+        if let Some(callback) = &self.on_event {
+            callback.call(event());
+        }
+    }
+}
+
+/// Progress notifications for library consumers, delivered through `PingConfig::on_event`
+#[derive(Debug, Clone)]
+pub enum PingEvent {
+    EndpointStarted { endpoint: String },
+    AttemptCompleted { endpoint: String, attempt: PingAttempt },
+    EndpointFinished { result: Box<PingResult> },
+}
+
+/// A shareable `FnMut(PingEvent)` hook. Endpoints are pinged concurrently, so calls are serialized.
+#[derive(Clone)]
+pub struct EventCallback(Arc<Mutex<dyn FnMut(PingEvent) + Send>>);
+
+impl EventCallback {
+    pub fn new(callback: impl FnMut(PingEvent) + Send + 'static) -> Self {
+        EventCallback(Arc::new(Mutex::new(callback)))
+    }
+
+    fn call(&self, event: PingEvent) {
+        // A callback that panicked earlier still gets later events rather than poisoning the run
+        let mut callback = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        callback(event);
+    }
+}
+
+/// A shareable switch for stopping runs early. Once cancelled, pings in flight are abandoned and
+/// no more are sent; each endpoint's result then covers only the pings that had finished.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation(Arc<CancellationState>);
+
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Cancellation::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once `cancel` has been called
+    pub async fn cancelled(&self) {
+        loop {
+            // Created before the check, so a `cancel` in between still wakes it
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl std::fmt::Debug for EventCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventCallback")
+    }
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
+        PingConfig {
+            method: PROBE_METHOD.to_string(),
+            params: serde_json::json!([]),
+            count: 4,
+            timeout_secs: 10,
+            rounding: Rounding::default(),
+            origin: None,
+            strict_content_type: false,
+            on_event: None,
+            success_if: None,
+            max_response_bytes: None,
+            local_address: None,
+            ping_spacing: None,
+            interval: Duration::ZERO,
+            capture_extremes: false,
+            concurrency: 1,
+            simulate_loss: None,
+            retries: 0,
+            retry_backoff: Duration::from_millis(100),
+            headers: Vec::new(),
+            proxy: None,
+            accept_invalid_certs: false,
+            id_start: 1,
+            warmup: 0,
+            batch: None,
+            connect_timeout_secs: None,
+            cancel: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            probe_connect: true,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum PingError { // Custom error type for core logic 
     #[error("Request failed: {0}")] 
     RequestError(#[from] reqwest::Error),
     #[error("JSON-RPC error: {0}")]
     JsonRpcError(String),
+    #[error("Rejected by CORS/origin policy ({0})")]
+    OriginRejected(u16),
+    #[error("Unexpected Content-Type: {0}")]
+    UnexpectedContentType(String),
+    #[error("Invalid JSON response: {0}")]
+    InvalidJson(serde_json::Error),
+    #[error("--success-if condition not met")]
+    PredicateFailed,
+    #[error("Response too large (over {0} bytes)")]
+    ResponseTooLarge(u64),
+    #[error("Simulated loss")]
+    SimulatedLoss,
+    #[error("WebSocket error: {0}")]
+    WebSocket(String),
+    #[error("WebSocket error: Timed out waiting for a response")]
+    WebSocketTimeout,
+    #[error("WebSocket error: Connection closed")]
+    WebSocketClosed,
+    #[error("Batch answered {0} of {1} calls")]
+    IncompleteBatch(usize, usize),
+}
+
+/// What a successful ping measured and received
+struct PingResponse {
+    latency: Duration,
+    result: String,
+    content_type: Option<String>,
+    /// Time spent deserializing the body, excluded from `latency`
+    parse_time: Duration,
+    /// Size of the body as received, before parsing
+    body_len: usize,
+    /// The raw body, only kept when `config.capture_extremes` is set
+    body: Option<Vec<u8>>,
 }
 
-type PingAttemptResult = Result<(Duration, String), PingError>;
+type PingAttemptResult = Result<PingResponse, PingError>;
 
 
 // --- CORE LOGIC ---
+/// One counted ping: `ping_once` with `config.retries`, unless `config.simulate_loss` drops this `index`
+async fn ping_attempt(client: &reqwest::Client, url: &str, config: &PingConfig, index: usize) -> PingAttemptResult {
+    let id = config.request_id(index);
+    let mut attempt = ping_once(client, url, config, id).await;
+    // Transport failures and HTTP error statuses are retried; a JSON-RPC error is the server's deliberate answer
+    for retry in 0..config.retries {
+        if !matches!(attempt, Err(PingError::RequestError(_))) {
+            break;
+        }
+        // Backing off gives a rate limit time to clear; the wait is outside the measured latency
+        tokio::time::sleep(retry_backoff(config.retry_backoff, retry)).await;
+        attempt = ping_once(client, url, config, id).await;
+    }
+    apply_simulated_loss(config, url, index, attempt)
+}
+
+/// Fails a successful attempt when `config.simulate_loss` picks this `index`
+fn apply_simulated_loss(config: &PingConfig, url: &str, index: usize, attempt: PingAttemptResult) -> PingAttemptResult {
+    match config.simulate_loss {
+        Some(loss) if attempt.is_ok() && loss.drops(url, index) => Err(PingError::SimulatedLoss),
+        _ => attempt,
+    }
+}
+
+/// `base * 2^retry`, capped at `MAX_RETRY_BACKOFF`, where `retry` counts from 0
+fn retry_backoff(base: Duration, retry: usize) -> Duration {
+    base.saturating_mul(2_u32.saturating_pow(retry as u32)).min(MAX_RETRY_BACKOFF)
+}
+
 /// Pings an endpoint ONCE and returns its latency and block number, or an error
-async fn ping_once(client: &reqwest::Client, url: &str) -> PingAttemptResult {
-    let request_payload = serde_json::json!({
+async fn ping_once(client: &reqwest::Client, url: &str, config: &PingConfig, id: u64) -> PingAttemptResult {
+    let call = |id: u64| serde_json::json!({
         "jsonrpc": "2.0",
-        "method": "eth_blockNumber",
-        "params": [],
-        "id": 1,
+        "method": config.method,
+        "params": config.params,
+        "id": id,
     });
+    let batch_ids: Vec<u64> = (0..config.batch.unwrap_or(0) as u64).map(|offset| id.wrapping_add(offset)).collect();
+    let request_payload = if batch_ids.is_empty() {
+        call(id)
+    } else {
+        serde_json::Value::Array(batch_ids.iter().map(|id| call(*id)).collect())
+    };
 
     let start = Instant::now();
 
@@ -61,11 +609,100 @@ async fn ping_once(client: &reqwest::Client, url: &str) -> PingAttemptResult {
 
     let latency = start.elapsed();
 
-    if !response.status().is_success() {
-        return Err(PingError::RequestError(response.error_for_status().unwrap_err()));
+    let status = response.status();
+    if !status.is_success() {
+        let error = response.error_for_status_ref().unwrap_err();
+        let body = response.text().await.unwrap_or_default();
+        if is_origin_rejection(status, &body) {
+            return Err(PingError::OriginRejected(status.as_u16()));
+        }
+        return Err(PingError::RequestError(error));
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if config.strict_content_type && !content_type.as_deref().is_some_and(is_json_content_type) {
+        let received = content_type.unwrap_or_else(|| "none".to_string());
+        return Err(PingError::UnexpectedContentType(received));
+    }
+
+    // Download and parse separately, so slow deserialization of huge responses shows up on its own
+    let body = read_body(response, config.max_response_bytes).await?;
+    interpret_response(body, latency, status.as_u16(), content_type, &batch_ids, config)
+}
+
+/// The endpoint's connect time for `connect_ms`, probed once before its counted pings.
+///
+/// reqwest doesn't say when a pooled request's connection was set up, so a throwaway probe
+/// connection is timed on its own, outside any ping's latency. Behind a proxy the endpoint may
+/// not be reachable directly at all, so there's nothing meaningful to probe.
+async fn measure_connect(url: &str, config: &PingConfig) -> Option<Duration> {
+    if !config.probe_connect || uses_proxy(config) {
+        return None;
     }
+    config.unless_cancelled(probe_connect(url, config)).await.flatten()
+}
 
-    let json_response: serde_json::Value = response.json().await.map_err(PingError::RequestError)?;
+/// Times a DNS lookup and plain TCP connect to the endpoint's host, from `config.local_address` if set.
+/// TLS isn't negotiated, so for `https://` this is the network path alone.
+async fn probe_connect(url: &str, config: &PingConfig) -> Option<Duration> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    // IPv6 hosts come back bracketed, which the resolver doesn't accept
+    let host = parsed.host_str()?.trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = parsed.port_or_known_default()?;
+
+    let start = Instant::now();
+    let connect = async {
+        let addr = tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .ok()?
+            .find(|addr| config.local_address.is_none_or(|local| local.is_ipv4() == addr.is_ipv4()))?;
+        let socket = if addr.is_ipv4() { tokio::net::TcpSocket::new_v4() } else { tokio::net::TcpSocket::new_v6() }.ok()?;
+        if let Some(local) = config.local_address {
+            socket.bind((local, 0).into()).ok()?;
+        }
+        socket.connect(addr).await.ok()
+    };
+    tokio::time::timeout(config.connect_timeout().min(MAX_CONNECT_PROBE), connect).await.ok()??;
+    Some(start.elapsed())
+}
+
+/// Parses a JSON-RPC response body and decides whether the ping succeeded. For a batch sent with
+/// `batch_ids`, every call must be answered, and the first call's answer is judged.
+fn interpret_response(
+    body: Vec<u8>,
+    latency: Duration,
+    status: u16,
+    content_type: Option<String>,
+    batch_ids: &[u64],
+    config: &PingConfig,
+) -> PingAttemptResult {
+    let body_len = body.len();
+    let parse_start = Instant::now();
+    let json_response: serde_json::Value = serde_json::from_slice(&body).map_err(PingError::InvalidJson)?;
+    let json_response = if batch_ids.is_empty() { json_response } else { unbatch(json_response, batch_ids)? };
+    let parse_time = parse_start.elapsed();
+
+    // A custom predicate replaces the default "has a result and no error" rule
+    if let Some(predicate) = &config.success_if {
+        let vars = PredicateVars {
+            latency: duration_ms(&latency),
+            status,
+            result: json_response.get("result").cloned().unwrap_or_default(),
+            error: json_response.get("error").map(|error| match error.get("message").and_then(|m| m.as_str()) {
+                Some(message) => message.to_string(),
+                None => error.to_string(),
+            }),
+        };
+        if !predicate.evaluate(&vars) {
+            return Err(PingError::PredicateFailed);
+        }
+        let body = config.capture_extremes.then_some(body);
+        return Ok(PingResponse { latency, result: vars.result.to_string(), content_type, parse_time, body_len, body });
+    }
     
     if let Some(error) = json_response.get("error") {
         return Err(PingError::JsonRpcError(error.to_string()));
@@ -73,98 +710,707 @@ async fn ping_once(client: &reqwest::Client, url: &str) -> PingAttemptResult {
     
     if let Some(result) = json_response.get("result") {
         // We have a success! Return the latency and the block number string.
-        Ok((latency, result.to_string()))
+        let body = config.capture_extremes.then_some(body);
+        Ok(PingResponse { latency, result: result.to_string(), content_type, parse_time, body_len, body })
     } else {
         Err(PingError::JsonRpcError("Missing 'result' field in response".to_string()))
     }
 }
 
-/// Pings an endpoint multiple times and aggregates the results
-pub async fn ping_endpoint_multiple(url: &str, count: usize, timeout_secs: u64) -> PingResult {    
-    let client =  match reqwest::Client::builder()
-    .timeout(Duration::from_secs(timeout_secs))
-    .build() {
-        Ok(c) => c,
-        Err(e) => {
-            // If we can't even build the client, the entire process has failed.
-            // We return a failure PingResult immediately.
-            return PingResult {
-                endpoint: url.to_string(),
-                status: PingStatus::Failure,
-                error_message: Some(format!("Failed to build HTTP client: {}", e)),
-                // ... all other fields are None or 0 ...
-                avg_latency_ms: None,
-                min_latency_ms: None,
-                max_latency_ms: None,
-                block_number: None,
-                ping_count: count,
-                success_count: 0,
-            };
+/// Checks a batch answer has a response for each of `ids`, in any order, and none of them is an
+/// error, then picks out the response to the first id
+fn unbatch(json: serde_json::Value, ids: &[u64]) -> Result<serde_json::Value, PingError> {
+    let serde_json::Value::Array(responses) = json else {
+        // Servers that don't support batches tend to answer with a single error object
+        return match json.get("error") {
+            Some(error) => Err(PingError::JsonRpcError(error.to_string())),
+            None => Err(PingError::IncompleteBatch(0, ids.len())),
+        };
+    };
+    let answer_id = |response: &serde_json::Value| response.get("id").and_then(|v| v.as_u64());
+    let answered = ids.iter().filter(|id| responses.iter().any(|r| answer_id(r) == Some(**id))).count();
+    if answered < ids.len() {
+        return Err(PingError::IncompleteBatch(answered, ids.len()));
+    }
+    if let Some(error) = responses.iter().find_map(|r| r.get("error")) {
+        return Err(PingError::JsonRpcError(error.to_string()));
+    }
+    responses
+        .into_iter()
+        .find(|r| answer_id(r) == ids.first().copied())
+        .ok_or(PingError::IncompleteBatch(0, ids.len()))
+}
+
+/// Reads the response body chunk by chunk, giving up as soon as it exceeds `limit` bytes
+async fn read_body(mut response: reqwest::Response, limit: Option<u64>) -> Result<Vec<u8>, PingError> {
+    let Some(limit) = limit else {
+        return Ok(response.bytes().await.map_err(PingError::RequestError)?.to_vec());
+    };
+    // No point downloading anything if the server already told us it's too big
+    if response.content_length().is_some_and(|length| length > limit) {
+        return Err(PingError::ResponseTooLarge(limit));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(PingError::RequestError)? {
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(PingError::ResponseTooLarge(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Asks an endpoint for its `eth_chainId`. Any failure along the way gives `None`.
+pub async fn fetch_chain_id(client: &reqwest::Client, url: &str) -> Option<u64> {
+    fetch_quantity(client, url, "eth_chainId").await
+}
+
+/// Asks an endpoint for its `net_peerCount`. Endpoints that don't support the method give `None`.
+pub async fn fetch_peer_count(client: &reqwest::Client, url: &str) -> Option<u64> {
+    fetch_quantity(client, url, "net_peerCount").await
+}
+
+/// Asks an endpoint for `eth_syncing`, which is `false` once synced and an object of progress
+/// while syncing. Endpoints that don't support the method, or answer anything else, give `None`.
+pub async fn fetch_sync_status(client: &reqwest::Client, url: &str) -> Option<SyncStatus> {
+    match fetch_result(client, url, "eth_syncing").await? {
+        serde_json::Value::Bool(false) => Some(SyncStatus::Synced),
+        serde_json::Value::Object(_) => Some(SyncStatus::Syncing),
+        _ => None,
+    }
+}
+
+/// Asks an endpoint for its `eth_gasPrice` in wei. Any failure along the way gives `None`.
+pub async fn fetch_gas_price(client: &reqwest::Client, url: &str) -> Option<u128> {
+    let raw = fetch_result(client, url, "eth_gasPrice").await?;
+    let hex = raw.as_str()?;
+    let digits = hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X"))?;
+    u128::from_str_radix(digits, 16).ok()
+}
+
+/// Calls a parameterless method whose result is a hex quantity, giving `None` on any failure
+async fn fetch_quantity(client: &reqwest::Client, url: &str, method: &str) -> Option<u64> {
+    decode_block_number(fetch_result(client, url, method).await?.as_str()?)
+}
+
+/// Calls a parameterless method for its `result`, giving `None` on any failure
+async fn fetch_result(client: &reqwest::Client, url: &str, method: &str) -> Option<serde_json::Value> {
+    let request_payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": [],
+        "id": 1,
+    });
+    let mut response: serde_json::Value = client.post(url).json(&request_payload).send().await.ok()?.json().await.ok()?;
+    Some(response.get_mut("result")?.take())
+}
+
+/// Decodes a hex quantity such as `0x13a4f2b` (optionally JSON-quoted) into a block height
+pub fn decode_block_number(raw: &str) -> Option<u64> {
+    let hex = raw.trim().trim_matches('"');
+    let digits = hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X"))?;
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// Identifies an endpoint across runs even when its API key or query string changes.
+///
+/// Hashes the host, port and path, skipping the query, credentials and any path segment that
+/// looks like a key (16+ URL-safe characters including a digit), so
+/// `https://mainnet.infura.io/v3/<key>` keeps its id when the key rotates.
+pub fn endpoint_id(url: &str) -> String {
+    let normalized = match reqwest::Url::parse(url) {
+        Ok(parsed) => {
+            let segments: Vec<&str> = parsed
+                .path_segments()
+                .into_iter()
+                .flatten()
+                .filter(|segment| !segment.is_empty() && !looks_like_key(segment))
+                .collect();
+            let port = parsed.port().map(|port| format!(":{}", port)).unwrap_or_default();
+            format!("{}{}/{}", parsed.host_str().unwrap_or(""), port, segments.join("/"))
+        }
+        Err(_) => url.trim().to_string(),
+    };
+
+    // FNV-1a, which unlike std's hasher is guaranteed to give the same value on every build
+    let hash = normalized.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn looks_like_key(segment: &str) -> bool {
+    segment.len() >= 16
+        && segment.chars().any(|c| c.is_ascii_digit())
+        && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// True for `application/json` and structured `+json` media types, ignoring parameters like charset
+pub fn is_json_content_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    media_type == "application/json" || media_type.ends_with("+json")
+}
+
+/// Gateways that only allow browser origins typically answer 401/403 with a body naming the policy
+fn is_origin_rejection(status: reqwest::StatusCode, body: &str) -> bool {
+    if status != reqwest::StatusCode::FORBIDDEN && status != reqwest::StatusCode::UNAUTHORIZED {
+        return false;
+    }
+    let body = body.to_lowercase();
+    ["origin", "cors", "referer", "referrer"].iter().any(|pattern| body.contains(pattern))
+}
+
+/// Builds the HTTP client used for pinging, applying the timeout and header settings from `config`
+pub fn build_client(config: &PingConfig) -> Result<reqwest::Client, String> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(origin) = &config.origin {
+        let value = reqwest::header::HeaderValue::from_str(origin).map_err(|e| e.to_string())?;
+        headers.insert(reqwest::header::ORIGIN, value.clone());
+        headers.insert(reqwest::header::REFERER, value);
+    }
+    for (name, value) in &config.headers {
+        let name = reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+        let value = reqwest::header::HeaderValue::from_str(value).map_err(|e| e.to_string())?;
+        headers.insert(name, value);
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .connect_timeout(config.connect_timeout())
+        // Set before the default headers, which replace it if they have a User-Agent of their own
+        .user_agent(&config.user_agent)
+        .default_headers(headers)
+        .local_address(config.local_address)
+        .danger_accept_invalid_certs(config.accept_invalid_certs);
+    // Without an explicit proxy, reqwest already picks one up from the environment
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|e| format!("invalid proxy '{}': {}", proxy, e))?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// True when requests go through a proxy, given explicitly or through the environment
+fn uses_proxy(config: &PingConfig) -> bool {
+    config.proxy.is_some()
+        || ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+            .iter()
+            .any(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()))
+}
+
+/// Running totals for the pings against one endpoint, folded into a `PingResult` at the end
+#[derive(Default)]
+struct Tally {
+    latencies: Vec<Duration>,
+    parse_times: Vec<Duration>,
+    /// The endpoint's probed connect time, see `measure_connect`
+    connect_time: Option<Duration>,
+    response_sizes: Vec<usize>,
+    successes: usize,
+    last_result: Option<String>,
+    last_error_message: Option<String>,
+    last_error_kind: Option<ErrorKind>,
+    error_counts: BTreeMap<String, usize>,
+    distinct_errors: Vec<String>,
+    last_content_type: Option<String>,
+    attempts: Vec<PingAttempt>,
+    fastest_response: Option<CapturedBody>,
+    slowest_response: Option<CapturedBody>,
+    /// When the counted pings began, for `time_to_first_success`
+    started: Option<Instant>,
+    time_to_first_success: Option<Duration>,
+}
+
+impl Tally {
+    /// An empty tally for pings about to be sent; time to first success counts from now
+    fn start() -> Self {
+        Tally { started: Some(Instant::now()), ..Tally::default() }
+    }
+
+    /// When nothing could be sent at all, every one of the `count` pings failed the same way
+    fn failed_before_sending(kind: ErrorKind, message: String, count: usize) -> Self {
+        Tally {
+            last_error_message: Some(message.clone()),
+            last_error_kind: Some(kind),
+            error_counts: BTreeMap::from([(kind.to_string(), count)]),
+            distinct_errors: vec![message],
+            ..Tally::default()
         }
-    }; 
+    }
 
-    let mut latencies = Vec::new();
-    let mut successes = 0;
-    let mut last_block_number = None;
-    let mut last_error_message = None;
+    /// Adds one finished ping, sent at `sent_at` and done at `completed_at`. Attempts can be
+    /// recorded in a different order than they completed in.
+    fn record(&mut self, attempt: &PingAttemptResult, sent_at: SystemTime, completed_at: Instant) {
+        self.attempts.push(PingAttempt {
+            index: self.attempts.len(),
+            timestamp_ms: sent_at.duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0),
+            latency_ms: attempt.as_ref().ok().map(|response| duration_ms(&response.latency)),
+            success: attempt.is_ok(),
+            result: attempt.as_ref().ok().map(|response| response.result.clone()),
+            error: attempt.as_ref().err().map(describe_error),
+        });
 
-    for _ in 0..count {
-        match ping_once(&client, url).await {
-            Ok((latency, block_number)) => {
-                successes += 1;
-                latencies.push(latency.as_millis());
-                last_block_number = Some(block_number);
+        match attempt {
+            Ok(response) => {
+                // Failed pings, their retries and backoff, and pacing before this one all count.
+                // The earliest completion wins, which under --concurrency needn't be the first recorded.
+                if let Some(started) = self.started {
+                    let elapsed = completed_at.saturating_duration_since(started);
+                    self.time_to_first_success = Some(self.time_to_first_success.map_or(elapsed, |first| first.min(elapsed)));
+                }
+                self.successes += 1;
+                self.latencies.push(response.latency);
+                self.parse_times.push(response.parse_time);
+                self.response_sizes.push(response.body_len);
+                self.last_result = Some(response.result.clone());
+                self.last_content_type = response.content_type.clone();
+                if let Some(body) = &response.body {
+                    let latency_ms = duration_ms(&response.latency);
+                    if self.fastest_response.as_ref().is_none_or(|fastest| latency_ms < fastest.latency_ms) {
+                        self.fastest_response = Some(CapturedBody { latency_ms, body: body.clone() });
+                    }
+                    if self.slowest_response.as_ref().is_none_or(|slowest| latency_ms > slowest.latency_ms) {
+                        self.slowest_response = Some(CapturedBody { latency_ms, body: body.clone() });
+                    }
+                }
             }
             Err(e) => {
-                if let PingError::RequestError(ref req_err) = e {
-                    if req_err.is_timeout() {
-                        last_error_message = Some("Request timed out".to_string());
-                    } else if req_err.is_connect() {
-                        last_error_message = Some("Connection failed".to_string());
-                    } else if let Some(status) = req_err.status() {
-                        last_error_message = Some(format!("HTTP Error: {}", status));
-                    } else {
-                        last_error_message = Some(e.to_string());
-                    }
-                } else {
-                    last_error_message = Some(e.to_string());
+                let message = describe_error(e);
+                if !self.distinct_errors.contains(&message) {
+                    self.distinct_errors.push(message.clone());
                 }
+                self.last_error_message = Some(message);
+                let kind = error_kind(e);
+                *self.error_counts.entry(kind.to_string()).or_insert(0) += 1;
+                self.last_error_kind = Some(kind);
             }
         }
     }
 
-    let status = if successes == count {
-        PingStatus::Success
-    } else if successes > 0 {
-        PingStatus::PartialSuccess
+    /// Like `into_result` for a run of `count` pings, except that a cancelled run is judged on the
+    /// pings that finished before it stopped
+    fn into_run_result(self, url: &str, count: usize, config: &PingConfig) -> PingResult {
+        let finished = self.attempts.len();
+        if !config.is_cancelled() || finished >= count {
+            return self.into_result(url, count, config.rounding);
+        }
+        let mut result = self.into_result(url, finished, config.rounding);
+        result.cancelled = true;
+        if finished == 0 {
+            result.status = PingStatus::Failure;
+            result.error_message = Some("Cancelled before any ping finished".to_string());
+        }
+        result
+    }
+
+    fn into_result(self, url: &str, count: usize, rounding: Rounding) -> PingResult {
+        let status = if self.successes == count {
+            PingStatus::Success
+        } else if self.successes > 0 {
+            PingStatus::PartialSuccess
+        } else {
+            PingStatus::Failure
+        };
+
+        let stats = calculate_stats(&self.latencies, rounding);
+        let avg_parse_ms = calculate_stats(&self.parse_times, rounding).avg_exact;
+        let connect_ms = self.connect_time.as_ref().map(duration_ms);
+        let avg_response_bytes = (!self.response_sizes.is_empty())
+            .then(|| self.response_sizes.iter().sum::<usize>() / self.response_sizes.len());
+
+        PingResult {
+            endpoint: url.to_string(),        
+            label: None,
+            endpoint_id: endpoint_id(url),
+            avg_latency_ms: stats.avg,
+            avg_latency_ms_exact: stats.avg_exact,
+            min_latency_ms: stats.min,
+            min_latency_ms_exact: stats.min_exact,
+            max_latency_ms: stats.max,
+            max_latency_ms_exact: stats.max_exact,
+            median_latency_ms: stats.median,
+            median_latency_ms_exact: stats.median_exact,
+            stddev_latency_ms: stats.stddev,
+            stddev_latency_ms_exact: stats.stddev_exact,
+            avg_latency_ci_ms: stats.avg_ci,
+            time_to_first_success_ms: self.time_to_first_success.map(|elapsed| rounding.apply(duration_ms(&elapsed))),
+            result: self.last_result,
+            block_height: None,
+            ping_count: count,
+            success_count: self.successes,
+            status,
+            error_message: self.last_error_message,
+            error_kind: self.last_error_kind,
+            error_counts: self.error_counts,
+            distinct_errors: self.distinct_errors,
+            tags: BTreeMap::new(),
+            from_cache: false,
+            cancelled: false,
+            below_min_height: false,
+            blocks_behind: None,
+            lagging: false,
+            chain_id: None,
+            peer_count: None,
+            sync_status: None,
+            gas_price_wei: None,
+            cold_latency_ms: None,
+            warm_latency_ms: None,
+            handshake_ms: None,
+            content_type: self.last_content_type,
+            avg_parse_ms,
+            avg_response_bytes,
+            connect_ms,
+            batch_size: None,
+            per_call_latency_ms: None,
+            attempts: self.attempts,
+            fastest_response: self.fastest_response,
+            slowest_response: self.slowest_response,
+        }
+    }
+}
+
+/// Turns a failed attempt into the short message shown to users
+fn describe_error(e: &PingError) -> String {
+    if let PingError::RequestError(req_err) = e {
+        if req_err.is_timeout() {
+            let message = if req_err.is_connect() {
+                "Connect timed out"
+            } else if req_err.is_body() {
+                "Timed out reading the response body"
+            } else {
+                "Request timed out"
+            };
+            return message.to_string();
+        } else if req_err.is_connect() {
+            return describe_connect_error(req_err).to_string();
+        } else if let Some(status) = req_err.status() {
+            return format!("HTTP Error: {}", status);
+        }
+    }
+    e.to_string()
+}
+
+/// The category `describe_error` would put a failed attempt in
+fn error_kind(e: &PingError) -> ErrorKind {
+    match e {
+        PingError::RequestError(req_err) if req_err.is_timeout() && req_err.is_connect() => ErrorKind::ConnectTimeout,
+        PingError::RequestError(req_err) if req_err.is_timeout() => ErrorKind::Timeout,
+        PingError::RequestError(req_err) if req_err.is_connect() => ErrorKind::ConnectionFailed,
+        PingError::RequestError(req_err) => match req_err.status() {
+            Some(status) => ErrorKind::HttpStatus(status.as_u16()),
+            None => ErrorKind::Other,
+        },
+        PingError::OriginRejected(status) => ErrorKind::HttpStatus(*status),
+        PingError::WebSocketTimeout => ErrorKind::Timeout,
+        PingError::WebSocketClosed => ErrorKind::ConnectionFailed,
+        PingError::JsonRpcError(_) | PingError::IncompleteBatch(..) => ErrorKind::JsonRpc,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Tells DNS, TCP and TLS failures apart by walking the error's source chain
+fn describe_connect_error(err: &reqwest::Error) -> &'static str {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = std::error::Error::source(err);
+    let mut text = String::new();
+    while let Some(cause) = source {
+        if let Some(io_err) = cause.downcast_ref::<std::io::Error>()
+            && io_err.kind() == std::io::ErrorKind::ConnectionRefused
+        {
+            return "Connection refused";
+        }
+        if let Some(io_err) = cause.downcast_ref::<std::io::Error>()
+            && io_err.kind() == std::io::ErrorKind::AddrNotAvailable
+        {
+            return "Local address not available";
+        }
+        text.push_str(&cause.to_string().to_ascii_lowercase());
+        text.push('\n');
+        source = cause.source();
+    }
+
+    // hyper labels its own connect stages; anything else that mentions TLS came from the handshake
+    if text.contains("dns error") {
+        "DNS lookup failed"
+    } else if text.contains("tcp connect error") {
+        "Connection failed"
+    } else if ["tls", "ssl", "certificate", "handshake"].iter().any(|word| text.contains(word)) {
+        "TLS handshake failed"
+    } else {
+        "Connection failed"
+    }
+}
+
+/// If we can't even build the client, the entire process has failed
+fn client_failure(url: &str, count: usize, error: String) -> PingResult {
+    let message = format!("Failed to build HTTP client: {}", error);
+    Tally::failed_before_sending(ErrorKind::ClientBuild, message, count).into_result(url, count, Rounding::default())
+}
+
+/// Pings an endpoint multiple times and aggregates the results
+pub async fn ping_endpoint_multiple(url: &str, config: &PingConfig) -> PingResult {
+    ping_endpoint_detailed(url, config).await.into()
+}
+
+/// Like `ping_endpoint_multiple`, but keeps every individual attempt alongside the summary
+pub async fn ping_endpoint_detailed(url: &str, config: &PingConfig) -> DetailedPingResult {
+    let mut summary = match build_client(config) {
+        Ok(client) => ping_endpoint_with_client(&client, url, config).await,
+        Err(e) => {
+            config.emit(|| PingEvent::EndpointStarted { endpoint: url.to_string() });
+            finish(config, client_failure(url, config.count, e))
+        }
+    };
+    let attempts = std::mem::take(&mut summary.attempts);
+    DetailedPingResult { summary, attempts }
+}
+
+/// Like `ping_endpoint_multiple`, but over an existing client so its warm connections are reused
+pub async fn ping_endpoint_with_client(client: &reqwest::Client, url: &str, config: &PingConfig) -> PingResult {
+    // WebSocket endpoints keep one connection of their own for the whole run
+    if ws::is_websocket_url(url) {
+        return ws::ping_endpoint(url, config).await;
+    }
+    config.emit(|| PingEvent::EndpointStarted { endpoint: url.to_string() });
+
+    // Warm-up ids follow the counted pings' so they can't collide with any of them
+    for warmup in 0..config.warmup {
+        if config.unless_cancelled(ping_once(client, url, config, config.request_id(config.count + warmup))).await.is_none() {
+            break;
+        }
+    }
+
+    let connect_time = measure_connect(url, config).await;
+    let mut tally = Tally { connect_time, ..Tally::start() };
+    if config.concurrency > 1 {
+        // `buffered` keeps up to N requests in flight but hands them back in send order,
+        // so the tally sees the same sequence it would without concurrency
+        let mut pings = futures::stream::iter(0..config.count)
+            .map(|index| async move {
+                let sent_at = SystemTime::now();
+                let attempt = ping_attempt(client, url, config, index).await;
+                (attempt, sent_at, Instant::now())
+            })
+            .buffered(config.concurrency);
+        while let Some(Some((attempt, sent_at, completed_at))) = config.unless_cancelled(pings.next()).await {
+            tally.record(&attempt, sent_at, completed_at);
+            config.emit(|| attempt_completed(url, &tally));
+        }
     } else {
-        PingStatus::Failure
+        let mut last_start = None;
+        for index in 0..config.count {
+            if config.unless_cancelled(pace(config, &mut last_start)).await.is_none() {
+                break;
+            }
+            let sent_at = SystemTime::now();
+            let Some(attempt) = config.unless_cancelled(ping_attempt(client, url, config, index)).await else {
+                break;
+            };
+            tally.record(&attempt, sent_at, Instant::now());
+            config.emit(|| attempt_completed(url, &tally));
+        }
+    }
+
+    finish(config, tally.into_run_result(url, config.count, config))
+}
+
+/// Waits before every ping but the first, whether or not the previous one succeeded.
+///
+/// `config.ping_spacing` counts from the previous ping's start, so a slow response eats into it;
+/// `config.interval` is a fixed pause on top. Neither is part of the measured latency.
+async fn pace(config: &PingConfig, last_start: &mut Option<Instant>) {
+    if let Some(previous) = *last_start {
+        if let Some(spacing) = config.ping_spacing {
+            tokio::time::sleep(spacing.saturating_sub(previous.elapsed())).await;
+        }
+        if !config.interval.is_zero() {
+            tokio::time::sleep(config.interval).await;
+        }
+    }
+    *last_start = Some(Instant::now());
+}
+
+fn attempt_completed(url: &str, tally: &Tally) -> PingEvent {
+    let attempt = tally.attempts.last().cloned().expect("an attempt was just recorded");
+    PingEvent::AttemptCompleted { endpoint: url.to_string(), attempt }
+}
+
+fn finish(config: &PingConfig, mut result: PingResult) -> PingResult {
+    if config.method == PROBE_METHOD {
+        result.block_height = result.result.as_deref().and_then(decode_block_number);
+    }
+    if let Some(size) = config.batch && !ws::is_websocket_url(&result.endpoint) {
+        result.batch_size = Some(size);
+        result.per_call_latency_ms = result.avg_latency_ms_exact.map(|avg| avg / size as f64);
+    }
+    config.emit(|| PingEvent::EndpointFinished { result: Box::new(result.clone()) });
+    result
+}
+
+/// Pings an endpoint once over a fresh connection, then once more reusing it.
+///
+/// The difference between the two is the connection setup (DNS, TCP, TLS) overhead.
+pub async fn ping_endpoint_cold_warm(url: &str, config: &PingConfig) -> PingResult {
+    config.emit(|| PingEvent::EndpointStarted { endpoint: url.to_string() });
+
+    // A new client per endpoint has an empty connection pool, so the first ping pays the handshake
+    let client = match build_client(config) {
+        Ok(c) => c,
+        Err(e) => return finish(config, client_failure(url, 2, e)),
     };
-    
-    let (avg, min, max) = calculate_stats(&latencies);
 
-    PingResult {
-        endpoint: url.to_string(),        
-        avg_latency_ms: avg,
-        min_latency_ms: min,
-        max_latency_ms: max,
-        block_number: last_block_number,
-        ping_count: count,
-        success_count: successes,
-        status,
-        error_message: last_error_message,
+    let connect_time = measure_connect(url, config).await;
+    let mut tally = Tally { connect_time, ..Tally::start() };
+    let cold_sent_at = SystemTime::now();
+    let cold = config.unless_cancelled(ping_attempt(&client, url, config, 0)).await;
+    if let Some(cold) = &cold {
+        tally.record(cold, cold_sent_at, Instant::now());
+        config.emit(|| attempt_completed(url, &tally));
+    }
+    let warm_sent_at = SystemTime::now();
+    let warm = match cold {
+        Some(_) => config.unless_cancelled(ping_attempt(&client, url, config, 1)).await,
+        None => None,
+    };
+    if let Some(warm) = &warm {
+        tally.record(warm, warm_sent_at, Instant::now());
+        config.emit(|| attempt_completed(url, &tally));
+    }
+    let mut result = tally.into_run_result(url, 2, config);
+
+    let latency_ms = |attempt: &Option<PingAttemptResult>| attempt.as_ref()?.as_ref().ok().map(|response| duration_ms(&response.latency));
+    let (cold_ms, warm_ms) = (latency_ms(&cold), latency_ms(&warm));
+    result.cold_latency_ms = cold_ms.map(|ms| config.rounding.apply(ms));
+    result.warm_latency_ms = warm_ms.map(|ms| config.rounding.apply(ms));
+    // From the exact latencies, so a sub-millisecond handshake isn't lost to rounding each side first
+    result.handshake_ms = cold_ms.zip(warm_ms).map(|(cold, warm)| config.rounding.apply_signed(cold - warm));
+    finish(config, result)
+}
+
+/// Alternates fresh and reused connections across `config.count` pings: odd pings (1st, 3rd, ...)
+/// each get a brand-new connection, even pings reuse one kept warm for the whole run.
+///
+/// Interleaving cancels out drift over the run. The fresh and reused averages are reported as
+/// `cold_latency_ms` and `warm_latency_ms`, and their difference as `handshake_ms`.
+pub async fn ping_endpoint_alternating(url: &str, config: &PingConfig) -> PingResult {
+    config.emit(|| PingEvent::EndpointStarted { endpoint: url.to_string() });
+
+    let reused_client = match build_client(config) {
+        Ok(c) => c,
+        Err(e) => return finish(config, client_failure(url, config.count, e)),
+    };
+    // Open the reused connection up front (not counted), so the first even ping really reuses it.
+    // Its id comes after the counted pings' so it can't collide with any of them.
+    let _ = config.unless_cancelled(ping_once(&reused_client, url, config, config.request_id(config.count))).await;
+
+    let connect_time = measure_connect(url, config).await;
+    let mut tally = Tally { connect_time, ..Tally::start() };
+    let (mut fresh, mut reused) = (Vec::new(), Vec::new());
+    let mut last_start = None;
+    for index in 0..config.count {
+        if config.unless_cancelled(pace(config, &mut last_start)).await.is_none() {
+            break;
+        }
+        let is_fresh = index % 2 == 0;
+        // A new client has an empty pool, so its only request pays the full connection setup
+        let client = if is_fresh {
+            match build_client(config) {
+                Ok(c) => c,
+                Err(e) => return finish(config, client_failure(url, config.count, e)),
+            }
+        } else {
+            reused_client.clone()
+        };
+        let sent_at = SystemTime::now();
+        let Some(attempt) = config.unless_cancelled(ping_attempt(&client, url, config, index)).await else {
+            break;
+        };
+        if let Ok(response) = &attempt {
+            if is_fresh { &mut fresh } else { &mut reused }.push(response.latency);
+        }
+        tally.record(&attempt, sent_at, Instant::now());
+        config.emit(|| attempt_completed(url, &tally));
     }
+
+    let mut result = tally.into_run_result(url, config.count, config);
+    let (fresh, reused) = (calculate_stats(&fresh, config.rounding), calculate_stats(&reused, config.rounding));
+    result.cold_latency_ms = fresh.avg;
+    result.warm_latency_ms = reused.avg;
+    result.handshake_ms = fresh.avg_exact.zip(reused.avg_exact).map(|(cold, warm)| config.rounding.apply_signed(cold - warm));
+    finish(config, result)
 }
 
-fn calculate_stats(latencies: &[u128]) -> (Option<u128>, Option<u128>, Option<u128>) {
+/// Summary statistics over the successful latencies of one endpoint
+#[derive(Debug, Default, PartialEq)]
+struct LatencyStats {
+    avg: Option<u128>,
+    avg_exact: Option<f64>,
+    min: Option<u128>,
+    min_exact: Option<f64>,
+    max: Option<u128>,
+    max_exact: Option<f64>,
+    median: Option<u128>,
+    median_exact: Option<f64>,
+    stddev: Option<u128>,
+    stddev_exact: Option<f64>,
+    avg_ci: Option<(u128, u128)>,
+}
+
+/// Below this many samples the standard deviation is too unreliable for an interval to mean much
+const MIN_CI_SAMPLES: usize = 5;
+
+/// Works on full-resolution durations and only converts to (fractional) milliseconds at the end,
+/// so sub-millisecond endpoints don't all collapse to 0ms
+fn calculate_stats(latencies: &[Duration], rounding: Rounding) -> LatencyStats {
     if latencies.is_empty() {
-        return (None, None, None);
+        return LatencyStats::default();
+    }
+    let sum: u128 = latencies.iter().map(Duration::as_nanos).sum();
+    // Floating-point mean, so fractional milliseconds aren't silently truncated
+    let avg_exact = sum as f64 / latencies.len() as f64 / 1_000_000.0;
+    let min_exact = latencies.iter().min().map(duration_ms);
+    let max_exact = latencies.iter().max().map(duration_ms);
+    let median_exact = median(latencies);
+    // Population standard deviation: how much this endpoint's own pings varied
+    let stddev_exact = (latencies.iter().map(|d| (duration_ms(d) - avg_exact).powi(2)).sum::<f64>() / latencies.len() as f64).sqrt();
+
+    // Normal approximation: mean ± 1.96 standard errors
+    let avg_ci = (latencies.len() >= MIN_CI_SAMPLES).then(|| {
+        let n = latencies.len() as f64;
+        let variance = latencies.iter().map(|d| (duration_ms(d) - avg_exact).powi(2)).sum::<f64>() / (n - 1.0);
+        let margin = 1.96 * (variance / n).sqrt();
+        (rounding.apply((avg_exact - margin).max(0.0)), rounding.apply(avg_exact + margin))
+    });
+
+    LatencyStats {
+        avg: Some(rounding.apply(avg_exact)),
+        avg_exact: Some(avg_exact),
+        min: min_exact.map(|ms| rounding.apply(ms)),
+        min_exact,
+        max: max_exact.map(|ms| rounding.apply(ms)),
+        max_exact,
+        median: median_exact.map(|ms| rounding.apply(ms)),
+        median_exact,
+        stddev: Some(rounding.apply(stddev_exact)),
+        stddev_exact: Some(stddev_exact),
+        avg_ci,
+    }
+}
+
+/// The middle latency in fractional milliseconds, averaging the two middle ones for an even count
+fn median(latencies: &[Duration]) -> Option<f64> {
+    let mut sorted = latencies.to_vec();
+    sorted.sort();
+    let middle = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 0 => Some((duration_ms(&sorted[middle - 1]) + duration_ms(&sorted[middle])) / 2.0),
+        _ => Some(duration_ms(&sorted[middle])),
     }
-    let sum: u128 = latencies.iter().sum();
-    let avg = sum / latencies.len() as u128;
-    let min = latencies.iter().min().copied();
-    let max = latencies.iter().max().copied();
-    (Some(avg), min, max)
+}
+
+/// A duration as fractional milliseconds
+fn duration_ms(duration: &Duration) -> f64 {
+    duration.as_nanos() as f64 / 1_000_000.0
 }
 
 // --- TESTS ---
@@ -172,21 +1418,308 @@ fn calculate_stats(latencies: &[u128]) -> (Option<u128>, Option<u128>, Option<u1
 mod tests {
     use super::*;
 
+    fn millis(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|&ms| Duration::from_millis(ms)).collect()
+    }
+
     #[test]
     fn test_calculate_stats() {
-        let data = vec![100, 200, 300];
-        let (avg, min, max) = calculate_stats(&data);
-        assert_eq!(avg, Some(200));
-        assert_eq!(min, Some(100));
-        assert_eq!(max, Some(300));
+        let data = millis(&[100, 200, 300]);
+        let stats = calculate_stats(&data, Rounding::Nearest);
+        assert_eq!(stats.avg, Some(200));
+        assert_eq!(stats.avg_exact, Some(200.0));
+        assert_eq!(stats.min, Some(100));
+        assert_eq!(stats.max, Some(300));
+        // Population stddev of 100, 200, 300 is sqrt(20000 / 3) ~ 81.65
+        assert_eq!(stats.stddev, Some(82));
+        assert_eq!(calculate_stats(&millis(&[150]), Rounding::Nearest).stddev, Some(0));
+        assert_eq!(stats.median, Some(200));
+    }
+
+    #[test]
+    fn test_median_averages_middle_pair() {
+        assert_eq!(median(&millis(&[400, 100, 300, 200])), Some(250.0));
+        // One slow outlier moves the mean far more than the median
+        let stats = calculate_stats(&millis(&[100, 110, 2000]), Rounding::Nearest);
+        assert_eq!(stats.median, Some(110));
+        assert_eq!(stats.avg, Some(737));
+        assert_eq!(median(&[]), None);
+    }
+
+    #[test]
+    fn test_calculate_stats_confidence_interval() {
+        assert_eq!(calculate_stats(&millis(&[100, 200, 300, 400]), Rounding::Nearest).avg_ci, None);
+        // Mean 100, sample stddev ~15.81, so the margin is 1.96 * 15.81 / sqrt(5) ~ 13.86
+        let stats = calculate_stats(&millis(&[80, 90, 100, 110, 120]), Rounding::Nearest);
+        assert_eq!(stats.avg_ci, Some((86, 114)));
     }
 
     #[test]
     fn test_calculate_stats_empty() {
-        let data = vec![];
-        let (avg, min, max) = calculate_stats(&data);
-        assert_eq!(avg, None);
-        assert_eq!(min, None);
-        assert_eq!(max, None);
+        let stats = calculate_stats(&[], Rounding::Nearest);
+        assert_eq!(stats, LatencyStats::default());
+    }
+
+    #[test]
+    fn test_is_origin_rejection() {
+        let forbidden = reqwest::StatusCode::FORBIDDEN;
+        assert!(is_origin_rejection(forbidden, "Origin not allowed"));
+        assert!(is_origin_rejection(reqwest::StatusCode::UNAUTHORIZED, "{\"error\":\"CORS policy\"}"));
+        assert!(!is_origin_rejection(forbidden, "invalid api key"));
+        assert!(!is_origin_rejection(reqwest::StatusCode::BAD_GATEWAY, "origin unreachable"));
+    }
+
+    #[test]
+    fn test_decode_block_number() {
+        assert_eq!(decode_block_number("0x13a4f2b"), Some(20_598_571));
+        assert_eq!(decode_block_number("\"0x10\""), Some(16));
+        assert_eq!(decode_block_number("12345"), None);
+        assert_eq!(decode_block_number("0xzz"), None);
+    }
+
+    #[test]
+    fn test_endpoint_id_ignores_keys_and_query() {
+        let id = endpoint_id("https://mainnet.infura.io/v3/9aa3d95b3bc440fa88ea12eaa4456161");
+        assert_eq!(id, endpoint_id("https://MAINNET.infura.io/v3/0f1e2d3c4b5a69788796a5b4c3d2e1f0/"));
+        assert_eq!(endpoint_id("https://rpc.ankr.com/eth?apikey=abc"), endpoint_id("https://rpc.ankr.com/eth"));
+        assert_ne!(id, endpoint_id("https://sepolia.infura.io/v3/9aa3d95b3bc440fa88ea12eaa4456161"));
+        assert_ne!(endpoint_id("http://localhost:8545"), endpoint_id("http://localhost:8546"));
+        assert_eq!(id.len(), 16);
+    }
+
+    #[test]
+    fn test_time_to_first_success_includes_earlier_failures() {
+        let sent_at = UNIX_EPOCH + Duration::from_secs(1_000);
+        let response = PingResponse {
+            latency: Duration::from_millis(40),
+            result: "\"0x10\"".to_string(),
+            content_type: None,
+            parse_time: Duration::ZERO,
+            body_len: 45,
+            body: None,
+        };
+        let started = Instant::now();
+        let mut tally = Tally { started: Some(started), connect_time: Some(Duration::from_millis(12)), ..Tally::default() };
+        tally.record(&Err(PingError::SimulatedLoss), sent_at, started + Duration::from_millis(300));
+        tally.record(&Ok(response), sent_at + Duration::from_millis(300), started + Duration::from_millis(340));
+        let result = tally.into_result("http://node", 2, Rounding::Nearest);
+        assert_eq!(result.time_to_first_success_ms, Some(340));
+        assert_eq!(result.min_latency_ms, Some(40));
+        assert_eq!(result.connect_ms, Some(12.0));
+        assert_eq!(result.avg_response_bytes, Some(45));
+    }
+
+    #[test]
+    fn test_time_to_first_success_is_earliest_completion() {
+        // Under --concurrency a slow first ping is recorded before a later one that finished sooner
+        let (sent_at, started) = (UNIX_EPOCH, Instant::now());
+        let response = |latency_ms| PingResponse {
+            latency: Duration::from_millis(latency_ms),
+            result: "\"0x10\"".to_string(),
+            content_type: None,
+            parse_time: Duration::ZERO,
+            body_len: 45,
+            body: None,
+        };
+        let mut tally = Tally { started: Some(started), ..Tally::default() };
+        tally.record(&Ok(response(900)), sent_at, started + Duration::from_millis(900));
+        tally.record(&Ok(response(50)), sent_at, started + Duration::from_millis(60));
+        let result = tally.into_result("http://node", 2, Rounding::Nearest);
+        assert_eq!(result.time_to_first_success_ms, Some(60));
+    }
+
+    #[test]
+    fn test_simulated_loss_is_seeded() {
+        let loss = SimulatedLoss { percent: 30.0, seed: 7 };
+        let dropped = |loss: SimulatedLoss| (0..1000).filter(|&i| loss.drops("http://node", i)).collect::<Vec<_>>();
+        assert_eq!(dropped(loss), dropped(loss));
+        assert_ne!(dropped(loss), dropped(SimulatedLoss { seed: 8, ..loss }));
+        assert!((250..350).contains(&dropped(loss).len()));
+        assert!(dropped(SimulatedLoss { percent: 0.0, ..loss }).is_empty());
+        assert_eq!(dropped(SimulatedLoss { percent: 100.0, ..loss }).len(), 1000);
+    }
+
+    #[test]
+    fn test_is_json_content_type() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("application/json; charset=utf-8"));
+        assert!(is_json_content_type("application/vnd.api+json"));
+        assert!(!is_json_content_type("text/plain"));
+        assert!(!is_json_content_type("text/html; charset=utf-8"));
+    }
+
+    #[tokio::test]
+    async fn test_event_callback_order() {
+        // Nothing listens on port 1, so both pings fail fast without leaving the machine
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let config = PingConfig {
+            count: 2,
+            timeout_secs: 1,
+            on_event: Some(EventCallback::new(move |event| sink.lock().unwrap().push(event))),
+            ..PingConfig::default()
+        };
+
+        let result = ping_endpoint_multiple("http://127.0.0.1:1", &config).await;
+        assert_eq!(result.status, PingStatus::Failure);
+        assert_eq!(result.error_message.as_deref(), Some("Connection refused"));
+        assert_eq!(result.distinct_errors, vec!["Connection refused"]);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0], PingEvent::EndpointStarted { .. }));
+        assert!(matches!(&events[1], PingEvent::AttemptCompleted { attempt, .. } if attempt.index == 0));
+        assert!(matches!(&events[2], PingEvent::AttemptCompleted { attempt, .. } if attempt.index == 1));
+        assert!(matches!(events[3], PingEvent::EndpointFinished { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_detailed_result_splits_out_attempts() {
+        let config = PingConfig { count: 2, timeout_secs: 1, ..PingConfig::default() };
+        let detailed = ping_endpoint_detailed("http://127.0.0.1:1", &config).await;
+        assert!(detailed.summary.attempts.is_empty());
+        assert_eq!(detailed.attempts.len(), 2);
+        assert!(detailed.attempts.iter().all(|a| a.result.is_none() && a.error.as_deref() == Some("Connection refused")));
+
+        let result = PingResult::from(detailed);
+        assert_eq!(result.attempts.len(), 2);
+        assert_eq!(result.status, PingStatus::Failure);
+    }
+
+    #[test]
+    fn test_error_kind_categories() {
+        assert_eq!(error_kind(&PingError::JsonRpcError("{}".to_string())), ErrorKind::JsonRpc);
+        assert_eq!(error_kind(&PingError::OriginRejected(403)), ErrorKind::HttpStatus(403));
+        assert_eq!(error_kind(&PingError::PredicateFailed), ErrorKind::Other);
+        assert_eq!(error_kind(&PingError::WebSocketTimeout), ErrorKind::Timeout);
+        assert_eq!(error_kind(&PingError::WebSocketClosed), ErrorKind::ConnectionFailed);
+        assert_eq!(ErrorKind::HttpStatus(429).to_string(), "http 429");
+        assert_eq!(ErrorKind::ConnectTimeout.to_string(), "connect timeout");
+        assert_eq!(serde_json::to_string(&ErrorKind::HttpStatus(429)).unwrap(), r#"{"HttpStatus":429}"#);
+    }
+
+    #[test]
+    fn test_request_ids_count_up_from_id_start() {
+        let config = PingConfig { id_start: 100, ..PingConfig::default() };
+        let ids: Vec<u64> = (0..3).map(|index| config.request_id(index)).collect();
+        assert_eq!(ids, vec![100, 101, 102]);
+        assert_eq!(PingConfig::default().request_id(0), 1);
+    }
+
+    #[test]
+    fn test_retry_backoff_doubles_up_to_cap() {
+        let base = Duration::from_millis(100);
+        let delays: Vec<u128> = (0..4).map(|retry| retry_backoff(base, retry).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800]);
+        assert_eq!(retry_backoff(base, 10), MAX_RETRY_BACKOFF);
+        assert_eq!(retry_backoff(base, 64), MAX_RETRY_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_retries_do_not_inflate_ping_count() {
+        let config = PingConfig { count: 2, timeout_secs: 1, retries: 2, retry_backoff: Duration::from_millis(1), ..PingConfig::default() };
+        let result = ping_endpoint_multiple("http://127.0.0.1:1", &config).await;
+        assert_eq!(result.ping_count, 2);
+        assert_eq!(result.attempts.len(), 2);
+        assert_eq!(result.error_message.as_deref(), Some("Connection refused"));
+        assert_eq!(result.error_kind, Some(ErrorKind::ConnectionFailed));
+        assert_eq!(result.error_counts, BTreeMap::from([("connection failed".to_string(), 2)]));
+    }
+
+    #[tokio::test]
+    async fn test_warmup_pings_are_not_counted() {
+        let config = PingConfig { count: 2, timeout_secs: 1, warmup: 3, ..PingConfig::default() };
+        let result = ping_endpoint_multiple("http://127.0.0.1:1", &config).await;
+        assert_eq!(result.ping_count, 2);
+        assert_eq!(result.attempts.len(), 2);
+        assert_eq!(result.distinct_errors, vec!["Connection refused".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_run_covers_finished_pings() {
+        let cancel = Cancellation::new();
+        cancel.cancel();
+        let config = PingConfig { count: 5, timeout_secs: 1, cancel: Some(cancel), ..PingConfig::default() };
+        let result = ping_endpoint_multiple("http://127.0.0.1:1", &config).await;
+        assert!(result.cancelled);
+        assert_eq!(result.ping_count, 0);
+        assert_eq!(result.status, PingStatus::Failure);
+        assert_eq!(result.error_message.as_deref(), Some("Cancelled before any ping finished"));
+    }
+
+    #[test]
+    fn test_unbatch_needs_every_answer() {
+        let answer = |id: u64| serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": "0x10" });
+        // Answers may come back in any order; the first id's is the one judged
+        let unordered = serde_json::json!([answer(3), answer(1), answer(2)]);
+        assert_eq!(unbatch(unordered, &[1, 2, 3]).unwrap()["id"], 1);
+
+        let partial = serde_json::json!([answer(1), answer(2)]);
+        assert!(matches!(unbatch(partial, &[1, 2, 3]), Err(PingError::IncompleteBatch(2, 3))));
+
+        let failed = serde_json::json!([answer(1), { "jsonrpc": "2.0", "id": 2, "error": { "code": -32005, "message": "limit" } }]);
+        assert!(matches!(unbatch(failed, &[1, 2]), Err(PingError::JsonRpcError(_))));
+
+        let unsupported = serde_json::json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32600, "message": "batch not supported" } });
+        assert!(matches!(unbatch(unsupported, &[1, 2]), Err(PingError::JsonRpcError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_peer_count_unreachable_is_none() {
+        let client = build_client(&PingConfig { timeout_secs: 1, ..PingConfig::default() }).unwrap();
+        assert_eq!(fetch_peer_count(&client, "http://127.0.0.1:1").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_bad_proxy_fails_client_build() {
+        let config = PingConfig { count: 2, proxy: Some("ftp://proxy.example".to_string()), ..PingConfig::default() };
+        let result = ping_endpoint_multiple("http://127.0.0.1:1", &config).await;
+        assert_eq!(result.status, PingStatus::Failure);
+        assert_eq!(result.error_kind, Some(ErrorKind::ClientBuild));
+        assert_eq!(result.error_counts, BTreeMap::from([("client build".to_string(), 2)]));
+        let message = result.error_message.unwrap();
+        assert!(message.starts_with("Failed to build HTTP client: invalid proxy 'ftp://proxy.example'"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_pings_are_all_tallied() {
+        let config = PingConfig { count: 5, timeout_secs: 1, concurrency: 3, ..PingConfig::default() };
+        let result = ping_endpoint_multiple("http://127.0.0.1:1", &config).await;
+        assert_eq!(result.ping_count, 5);
+        assert_eq!(result.success_count, 0);
+        let indices: Vec<usize> = result.attempts.iter().map(|a| a.index).collect();
+        assert_eq!(indices, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_calculate_stats_rounding() {
+        // Exact mean is 10.666..., which integer division used to truncate to 10
+        let data = millis(&[10, 11, 11]);
+        assert_eq!(calculate_stats(&data, Rounding::Nearest).avg, Some(11));
+        assert_eq!(calculate_stats(&data, Rounding::Floor).avg, Some(10));
+        assert_eq!(calculate_stats(&data, Rounding::Ceil).avg, Some(11));
+
+        let exact = calculate_stats(&data, Rounding::Floor).avg_exact.unwrap();
+        assert!((exact - 32.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_signed_rounding_keeps_sub_millisecond_differences() {
+        // A handshake of 0.6ms, or warm coming out 0.6ms slower from noise
+        assert_eq!(Rounding::Nearest.apply_signed(0.6), 1);
+        assert_eq!(Rounding::Nearest.apply_signed(-0.6), -1);
+        assert_eq!(Rounding::Floor.apply_signed(0.6), 0);
+        assert_eq!(Rounding::Ceil.apply_signed(0.4), 1);
+    }
+
+    #[test]
+    fn test_calculate_stats_sub_millisecond() {
+        // A localhost-fast endpoint: every sample is under 1ms
+        let data = vec![Duration::from_micros(250), Duration::from_micros(500), Duration::from_micros(750)];
+        let stats = calculate_stats(&data, Rounding::Floor);
+        assert_eq!(stats.avg, Some(0));
+        assert!((stats.avg_exact.unwrap() - 0.5).abs() < 1e-9);
+        assert!((stats.min_exact.unwrap() - 0.25).abs() < 1e-9);
+        assert!((stats.max_exact.unwrap() - 0.75).abs() < 1e-9);
     }
 }
\ No newline at end of file
@@ -1,8 +1,34 @@
 
+mod cache;
+mod daemon;
+mod fleet;
+mod monitor;
+mod output;
+
 use clap::Parser;
-use chain_ping::{ping_endpoint_multiple, PingStatus, PingResult};
-use futures::future::join_all;
-use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, Color, Cell};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chain_ping::predicate::Predicate;
+use chain_ping::{build_client, fetch_chain_id, fetch_gas_price, fetch_peer_count, fetch_sync_status, is_json_content_type, ping_endpoint_alternating, ping_endpoint_cold_warm, ping_endpoint_multiple, Cancellation, PingConfig, PingResult, Rounding, SimulatedLoss, DEFAULT_USER_AGENT, PROBE_METHOD};
+use cache::ResultCache;
+use fleet::FailOn;
+use output::{render, render_monitor_report, render_summary, Column, OutputFormat, RenderOptions, RunSummary, SortBy, SummaryFormat};
+use crossterm::cursor::{MoveTo, MoveToColumn};
+use crossterm::terminal::{Clear, ClearType};
+use futures::stream::StreamExt;
+
+/// Exit code when the run completed but a post-run check (such as --max-block-divergence) failed
+const EXIT_CHECK_FAILED: i32 = 3;
+/// Exit code when the run completed but an endpoint was unhealthy by `--fail-on`'s standard
+const EXIT_UNHEALTHY: i32 = 4;
+/// Exit code after Ctrl-C cut the run short, as shells report for SIGINT
+const EXIT_INTERRUPTED: i32 = 130;
+/// `--timeout` when none is given
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
 
 /// A high-performance CLI tool for benchmarking Ethereum RPC endpoints.
 #[derive(Parser)]
@@ -15,124 +41,1048 @@ struct Cli {
     #[arg(short, long, default_value = "4")]
     pings: usize,
 
-    /// Timeout limit for each individual request in seconds
-    #[arg(short, long, default_value = "10")]
-    timeout: u64,
+    /// Timeout limit for each individual request in seconds, connecting included
+    /// [default: 10, or --connect-timeout if that is longer]
+    #[arg(short, long)]
+    timeout: Option<u64>,
 
-    /// Output format: table or json 
+    /// Output format: table, json, ndjson, csv, csv-timeseries, junit or prometheus
     #[arg(short, long, default_value = "table")]
-    format: String,
+    format: OutputFormat,
+
+    /// Write results to this file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Format for the --output file; when set, --format still prints to the terminal
+    #[arg(long, requires = "output")]
+    output_format: Option<OutputFormat>,
+
+    /// How latencies are rounded to whole milliseconds: nearest, floor or ceil
+    #[arg(long, default_value = "nearest")]
+    rounding: Rounding,
+
+    /// Attach metadata to every result as key=value (repeatable)
+    #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+    tags: Vec<(String, String)>,
+
+    /// Comma-separated table columns to show, e.g. endpoint,status,avg,block
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<Column>,
+
+    /// Reuse a cached result if the endpoint was measured within this many seconds
+    #[arg(long, value_name = "SECS")]
+    cache_ttl: Option<u64>,
+
+    /// Send this Origin (and Referer) with every request, for browser-gated endpoints
+    #[arg(long, value_name = "URL", value_parser = parse_origin)]
+    origin: Option<String>,
+
+    /// Ping each endpoint once on a fresh connection and once reusing it, reporting the handshake cost (ignores --pings)
+    #[arg(long)]
+    cold_warm: bool,
+
+    /// Constrain the table to this many characters wide (defaults to the terminal width)
+    #[arg(long, value_name = "COLS")]
+    width: Option<u16>,
+
+    /// Treat responses that aren't served as application/json as failures
+    #[arg(long)]
+    strict_content_type: bool,
+
+    /// Print JSON output on a single line instead of pretty-printing it
+    #[arg(long)]
+    compact_json: bool,
+
+    /// Exit with an error if healthy endpoints' block heights differ by more than N blocks
+    #[arg(long, value_name = "N")]
+    max_block_divergence: Option<u64>,
+
+    /// Show the table as a ranked leaderboard with the gap to first place
+    #[arg(long)]
+    leaderboard: bool,
+
+    /// Run as a long-lived service answering POST /ping on this address (e.g. 127.0.0.1:9545)
+    #[arg(long, value_name = "ADDR")]
+    daemon: Option<SocketAddr>,
+
+    /// Custom success rule per ping, e.g. 'result != null && latency < 500'
+    #[arg(long, value_name = "EXPR")]
+    success_if: Option<Predicate>,
+
+    /// Fail pings whose response body is larger than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    max_response_bytes: Option<u64>,
+
+    /// Add a one-line summary after the table, or wrap JSON output in an object with the summary alongside the results
+    #[arg(long)]
+    summary: bool,
+
+    /// Only ping endpoints whose URL contains this text (repeatable)
+    #[arg(long, value_name = "SUBSTRING")]
+    only: Vec<String>,
+
+    /// Show how many endpoints report each block height
+    #[arg(long)]
+    block_distribution: bool,
+
+    /// Also print the results as JSON on stderr, moving the progress banner to stdout
+    #[arg(long)]
+    json_to_stderr: bool,
+
+    /// Flag endpoints whose head block is below this height as suspect
+    #[arg(long, value_name = "N")]
+    min_block_height: Option<u64>,
+
+    /// Count endpoints flagged by --min-block-height as failures
+    #[arg(long, requires = "min_block_height")]
+    fail_below_min_height: bool,
+
+    /// Show how the p95 estimate settles as pings accumulate; stable means the last change is within PCT% (--convergence=PCT, default 5)
+    #[arg(long, value_name = "PCT", num_args = 0..=1, require_equals = true, default_missing_value = "5")]
+    convergence: Option<f64>,
+
+    /// Draw tables with plain ASCII borders and markers, without colors
+    #[arg(long)]
+    ascii_only: bool,
+
+    /// Ping endpoints one at a time instead of all at once
+    #[arg(long)]
+    sequential: bool,
+
+    /// Alternate fresh and reused connections across --pings and report each population's average
+    #[arg(long, conflicts_with = "cold_warm")]
+    ab_connection: bool,
+
+    /// Exit with an error if healthy endpoints don't all report the same eth_chainId
+    #[arg(long)]
+    same_chain: bool,
+
+    /// Send requests from this local IP address, to choose the network interface
+    #[arg(long, value_name = "IP")]
+    local_address: Option<IpAddr>,
+
+    /// Space each endpoint's pings one block time apart (in seconds), so each one can see a new head
+    #[arg(long, value_name = "SECS", value_parser = parse_block_time)]
+    pace_to_block_time: Option<Duration>,
+
+    /// JSON-RPC method each ping calls
+    #[arg(long, default_value = PROBE_METHOD)]
+    method: String,
+
+    /// JSON array of params for --method, e.g. '["latest", false]'
+    #[arg(long, value_name = "JSON", default_value = "[]", value_parser = parse_params)]
+    params: serde_json::Value,
+
+    /// Pause this many milliseconds between an endpoint's pings, e.g. to stay under rate limits
+    #[arg(long, value_name = "MILLIS", default_value = "0", conflicts_with = "pace_to_block_time")]
+    interval: u64,
+
+    /// Save each endpoint's fastest and slowest response bodies into this directory
+    #[arg(long, value_name = "DIR")]
+    capture_extremes: Option<PathBuf>,
+
+    /// Allow up to N of each endpoint's pings in flight at once
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = parse_concurrency,
+        conflicts_with_all = ["interval", "pace_to_block_time", "cold_warm", "ab_connection"],
+    )]
+    concurrency: usize,
+
+    /// Also print the run summary (counts, fastest endpoint, duration) as text or json, on stderr
+    #[arg(long, value_name = "FORMAT")]
+    summary_format: Option<SummaryFormat>,
+
+    /// Testing aid: fail this percentage (0-100) of successful pings on purpose. Not real packet loss.
+    #[arg(long, value_name = "PERCENT", value_parser = parse_loss_percent)]
+    simulate_loss: Option<f64>,
+
+    /// Seed for --simulate-loss, to drop the same pings again. Random when omitted.
+    #[arg(long, requires = "simulate_loss")]
+    loss_seed: Option<u64>,
+
+    /// Show block numbers in the table as the hex the endpoint returned, instead of decimal
+    #[arg(long)]
+    raw_block: bool,
+
+    /// Comma-separated chain ids an endpoint may be on, e.g. 1,8453; endpoints on any other chain fail
+    #[arg(long, value_delimiter = ',', value_name = "IDS")]
+    allowed_chain_ids: Vec<u64>,
+
+    /// Show how many blocks each endpoint trails the highest head, highlighting those more than N behind
+    #[arg(long, value_name = "N")]
+    max_lag: Option<u64>,
+
+    /// Fail endpoints whose eth_chainId isn't N, e.g. 1 for Ethereum mainnet
+    #[arg(long, value_name = "N", conflicts_with = "allowed_chain_ids")]
+    expected_chain_id: Option<u64>,
+
+    /// Keep pinging for this many seconds, printing each cycle as NDJSON and a report at the end
+    #[arg(
+        long,
+        value_name = "SECS",
+        conflicts_with_all = [
+            "daemon", "cold_warm", "ab_connection", "cache_ttl", "format", "output", "output_format", "json_to_stderr",
+            "summary", "summary_format", "leaderboard", "columns", "convergence", "block_distribution", "capture_extremes",
+            "sort_by", "reverse", "max_block_divergence", "same_chain", "max_lag", "fail_on",
+        ]
+    )]
+    monitor: Option<u64>,
+
+    /// Seconds between the starts of --monitor cycles
+    #[arg(long, value_name = "SECS", default_value = "10", requires = "monitor", value_parser = clap::value_parser!(u64).range(1..))]
+    monitor_interval: u64,
+
+    /// Read more endpoint URLs from this file, one per line ('-' for stdin). Blank lines and '#' comments are skipped.
+    #[arg(long, value_name = "PATH")]
+    endpoints_file: Option<PathBuf>,
+
+    /// Retry a ping up to N times when the request fails (not on JSON-RPC errors) before counting it as failed
+    #[arg(long, value_name = "N", default_value = "0")]
+    retries: usize,
+
+    /// Milliseconds to wait before the first --retries retry, doubling for each later one (max 5s)
+    #[arg(long, value_name = "MILLIS", default_value = "100", requires = "retries")]
+    retry_backoff: u64,
+
+    /// Re-run the benchmark every N seconds until Ctrl-C, redrawing the table (JSON prints one line per run)
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["daemon", "monitor", "output"])]
+    watch: Option<u64>,
+
+    /// Send an extra HTTP header with every request, as "Name: Value" (repeatable), e.g. for API keys
+    #[arg(long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Send every request through this proxy, e.g. http://proxy:8080 or socks5://127.0.0.1:1080. Without it, HTTPS_PROXY and HTTP_PROXY are used.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Accept invalid and self-signed TLS certificates. Insecure; only for nodes you trust.
+    #[arg(short = 'k', long)]
+    insecure: bool,
+
+    /// JSON-RPC id of the first ping to each endpoint; each later ping uses the next id, so no two look alike to a cache
+    #[arg(long, value_name = "N", default_value = "1")]
+    id_start: u64,
+
+    /// Send N throwaway pings to each endpoint first, to open the connection; they count toward nothing
+    #[arg(long, value_name = "N", default_value = "0", conflicts_with_all = ["cold_warm", "ab_connection"])]
+    warmup: usize,
+
+    /// Exit with code 4 when an endpoint is unhealthy: partial (any failed ping), failure (every ping failed) or never
+    #[arg(long, value_name = "LEVEL", default_value = "failure")]
+    fail_on: FailOn,
+
+    /// Don't print progress and status messages on stderr; results, warnings and errors still appear
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Print tables without colors. Also set by a non-empty NO_COLOR environment variable.
+    #[arg(long)]
+    no_color: bool,
+
+    /// Order results by latency (fastest first), success (highest rate first), block (highest first),
+    /// gas (cheapest first, with --check-gas) or none (as given)
+    #[arg(long, value_name = "KEY", default_value = "latency")]
+    sort_by: SortBy,
+
+    /// Reverse the --sort-by order. Failed endpoints stay at the bottom when sorting by latency.
+    #[arg(long)]
+    reverse: bool,
+
+    /// Also ask each endpoint for its net_peerCount, shown in a Peers column
+    #[arg(long)]
+    check_peers: bool,
+
+    /// Show peer counts below N in yellow. Implies --check-peers.
+    #[arg(long, value_name = "N")]
+    min_peers: Option<u64>,
+
+    /// Also ask each endpoint for eth_syncing, shown in a Synced column
+    #[arg(long)]
+    check_sync: bool,
+
+    /// Count fully successful endpoints that are still syncing as partial successes
+    #[arg(long, requires = "check_sync")]
+    downgrade_syncing: bool,
+
+    /// Also ask each endpoint for eth_gasPrice, shown in gwei in a Gas Price column
+    #[arg(long)]
+    check_gas: bool,
+
+    /// Send each ping as one JSON-RPC batch of N identical calls, reporting the per-call latency too
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    batch: Option<u64>,
+
+    /// Limit on connecting (DNS, TCP and TLS) in seconds, within --timeout. Defaults to --timeout.
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    connect_timeout: Option<u64>,
+
+    /// User-Agent sent with every request. A User-Agent given with --header takes precedence.
+    #[arg(long, value_name = "STRING", default_value = DEFAULT_USER_AGENT, value_parser = parse_user_agent)]
+    user_agent: String,
+
+    /// Benchmark at most N endpoints at once; the rest wait their turn. Unlimited by default.
+    #[arg(long, value_name = "N", value_parser = parse_max_parallel, conflicts_with = "sequential")]
+    max_parallel: Option<usize>,
+}
+
+/// Parses a `--tag` argument, keeping keys usable as metric label names
+fn parse_tag(raw: &str) -> Result<(String, String), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("invalid tag '{}': expected key=value", raw))?;
+
+    let valid_key = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key {
+        return Err(format!("invalid tag key '{}': use letters, digits and underscores", key));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Parses `--pace-to-block-time` seconds, which may be fractional (e.g. 0.25 for a fast L2)
+fn parse_block_time(raw: &str) -> Result<Duration, String> {
+    let secs: f64 = raw.parse().map_err(|_| format!("invalid block time '{}': expected seconds", raw))?;
+    if !secs.is_finite() || secs <= 0.0 {
+        return Err(format!("invalid block time '{}': must be greater than zero", raw));
+    }
+    Ok(Duration::from_secs_f64(secs))
+}
+
+/// Parses `--params` as JSON, which has to be an array to be valid JSON-RPC positional params
+fn parse_params(raw: &str) -> Result<serde_json::Value, String> {
+    let params: serde_json::Value = serde_json::from_str(raw).map_err(|e| format!("invalid JSON: {}", e))?;
+    if !params.is_array() {
+        return Err(format!("expected a JSON array such as '[\"latest\", false]', got '{}'", raw));
+    }
+    Ok(params)
+}
+
+/// Parses `--concurrency`, which needs at least one request in flight to make progress
+fn parse_concurrency(raw: &str) -> Result<usize, String> {
+    match raw.parse() {
+        Ok(0) | Err(_) => Err(format!("invalid concurrency '{}': expected a whole number of at least 1", raw)),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Parses `--simulate-loss` as a percentage from 0 to 100
+fn parse_loss_percent(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("invalid loss '{}': expected a percentage from 0 to 100", raw)),
+    }
+}
+
+/// Parses a `--header` argument, checking the name and value are valid in an HTTP request
+fn parse_header(raw: &str) -> Result<(String, String), String> {
+    let (name, value) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("invalid header '{}': expected \"Name: Value\"", raw))?;
+    let (name, value) = (name.trim(), value.trim());
+
+    reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("invalid header name '{}'", name))?;
+    reqwest::header::HeaderValue::from_str(value).map_err(|_| format!("invalid value for header '{}'", name))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Parses `--max-parallel`, which needs room for at least one endpoint to make progress
+fn parse_max_parallel(raw: &str) -> Result<usize, String> {
+    match raw.parse() {
+        Ok(0) | Err(_) => Err(format!("invalid max parallel '{}': expected a whole number of at least 1", raw)),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Checks a `--user-agent` is a valid header value
+fn parse_user_agent(raw: &str) -> Result<String, String> {
+    reqwest::header::HeaderValue::from_str(raw).map_err(|_| format!("invalid user agent '{}'", raw))?;
+    Ok(raw.to_string())
+}
+
+/// Parses `--origin` into the `scheme://host[:port]` form browsers send
+fn parse_origin(raw: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(raw).map_err(|e| format!("invalid origin '{}': {}", raw, e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err(format!("invalid origin '{}': expected an http(s) URL", raw));
+    }
+    Ok(url.origin().ascii_serialization())
 }
 
 
 #[tokio::main]
 async fn main() {
     
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+
+    // The overall timeout covers connecting too, so it never defaults to less than --connect-timeout
+    let timeout = cli.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS.max(cli.connect_timeout.unwrap_or(0)));
+    if let Some(connect_timeout) = cli.connect_timeout
+        && connect_timeout > timeout
+    {
+        eprintln!("Error: --connect-timeout ({}s) can't be longer than --timeout ({}s)", connect_timeout, timeout);
+        std::process::exit(1);
+    }
+
+    let config = PingConfig {
+        method: cli.method.clone(),
+        params: cli.params.clone(),
+        count: cli.pings,
+        timeout_secs: timeout,
+        rounding: cli.rounding,
+        origin: cli.origin.clone(),
+        strict_content_type: cli.strict_content_type,
+        on_event: None,
+        success_if: cli.success_if.clone(),
+        max_response_bytes: cli.max_response_bytes,
+        local_address: cli.local_address,
+        ping_spacing: cli.pace_to_block_time,
+        interval: Duration::from_millis(cli.interval),
+        capture_extremes: cli.capture_extremes.is_some(),
+        concurrency: cli.concurrency,
+        retries: cli.retries,
+        retry_backoff: Duration::from_millis(cli.retry_backoff),
+        headers: cli.headers.clone(),
+        proxy: cli.proxy.clone(),
+        accept_invalid_certs: cli.insecure,
+        id_start: cli.id_start,
+        warmup: cli.warmup,
+        batch: cli.batch.map(|size| size as usize),
+        connect_timeout_secs: cli.connect_timeout,
+        cancel: None,
+        user_agent: cli.user_agent.clone(),
+        probe_connect: shows_connect_time(&cli),
+        simulate_loss: cli.simulate_loss.map(|percent| SimulatedLoss {
+            percent,
+            seed: cli.loss_seed.unwrap_or_else(random_seed),
+        }),
+    };
+
+    if config.accept_invalid_certs {
+        eprintln!("Warning: TLS certificate verification is disabled (--insecure); responses could come from anyone");
+    }
+
+    // Binding fails right away for an address this machine doesn't have, unlike a connect attempt
+    if let Some(addr) = cli.local_address
+        && let Err(e) = std::net::UdpSocket::bind((addr, 0))
+    {
+        eprintln!("Error: --local-address {} can't be used on this machine: {}", addr, e);
+        std::process::exit(1);
+    }
+
+    if let Some(addr) = cli.daemon {
+        // Daemon results get the same markup as a single run's
+        let quiet = cli.quiet;
+        let client = build_client(&config).ok();
+        let tags: Arc<BTreeMap<String, String>> = Arc::new(cli.tags.iter().cloned().collect());
+        let (cli, annotate_config) = (Arc::new(cli), Arc::new(config.clone()));
+        let mark_up: daemon::MarkUp = Arc::new(move |mut result, labels| {
+            let (cli, config, tags, client) = (cli.clone(), annotate_config.clone(), tags.clone(), client.clone());
+            Box::pin(async move {
+                annotate(&mut result, client.as_ref(), &cli, &config, &labels, &tags).await;
+                result
+            })
+        });
+        if let Err(e) = daemon::serve(addr, config, quiet, mark_up).await {
+            eprintln!("Error: Daemon failed on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+        return;
+    }
     
+    if let Some(path) = &cli.endpoints_file {
+        match read_endpoints_file(path) {
+            Ok(endpoints) => cli.endpoints.extend(endpoints),
+            Err(e) => {
+                eprintln!("Error: Failed to read endpoints from '{}': {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     if cli.endpoints.is_empty() {
         eprintln!("Error: At least one endpoint URL is required");
         std::process::exit(1);
     }
 
+    // A single expected chain is just an allowlist of one
+    if let Some(chain_id) = cli.expected_chain_id {
+        cli.allowed_chain_ids = vec![chain_id];
+    }
+    // There's nothing to compare against --min-peers without the peer counts
+    if cli.min_peers.is_some() {
+        cli.check_peers = true;
+    }
+
+    // These read each result as a head height, which only holds for eth_blockNumber
+    let block_checks = [
+        ("--max-block-divergence", cli.max_block_divergence.is_some()),
+        ("--min-block-height", cli.min_block_height.is_some()),
+        ("--block-distribution", cli.block_distribution),
+        ("--max-lag", cli.max_lag.is_some()),
+    ];
+    if cli.method != PROBE_METHOD
+        && let Some((flag, _)) = block_checks.iter().find(|(_, used)| *used)
+    {
+        eprintln!("Error: {} needs block numbers, so it can't be combined with --method {}", flag, cli.method);
+        std::process::exit(1);
+    }
+
+    // `label=url` endpoints are pinged by URL; the label is put back on the result afterwards
+    let mut labels = BTreeMap::new();
+    for endpoint in &mut cli.endpoints {
+        if let (Some(label), url) = split_label(endpoint) {
+            labels.insert(url.to_string(), label.to_string());
+            *endpoint = url.to_string();
+        }
+    }
+
+    // --only narrows a long endpoint list down to the ones being debugged
+    if !cli.only.is_empty() {
+        cli.endpoints.retain(|endpoint| {
+            let label = labels.get(endpoint).map(String::as_str).unwrap_or_default();
+            cli.only.iter().any(|pattern| endpoint.contains(pattern.as_str()) || label.contains(pattern.as_str()))
+        });
+        if cli.endpoints.is_empty() {
+            eprintln!("Error: No endpoints match --only {}", cli.only.join(", "));
+            std::process::exit(1);
+        }
+    }
+
+    // Say so loudly, so simulated failures are never mistaken for a real outage
+    if let Some(loss) = config.simulate_loss {
+        eprintln!("Warning: Simulating {}% loss; failures marked 'Simulated loss' are not real (--loss-seed {} to repeat)", loss.percent, loss.seed);
+    }
+
     let endpoint_str = if cli.endpoints.len() == 1 { "endpoint" } else { "endpoints" };
+
+    if let Some(total) = cli.monitor {
+        let every = Duration::from_secs(cli.monitor_interval);
+        if !cli.quiet {
+            eprintln!("Monitoring {} {} every {}s for {}s...", cli.endpoints.len(), endpoint_str, cli.monitor_interval, total);
+        }
+        // Ctrl-C ends the window early, and the report covers the cycles that ran
+        let cancellation = cancel_on_ctrl_c();
+        let config = &PingConfig { cancel: Some(cancellation.clone()), ..config.clone() };
+        let client = build_client(config).ok();
+        let tags: BTreeMap<String, String> = cli.tags.iter().cloned().collect();
+        let (cli, labels, tags, client) = (&cli, &labels, &tags, client.as_ref());
+        let mark_up = move |mut result: PingResult| async move {
+            annotate(&mut result, client, cli, config, labels, tags).await;
+            result
+        };
+        match monitor::run(&cli.endpoints, config, Duration::from_secs(total), every, cli.max_parallel, mark_up).await {
+            Ok(windows) => {
+                if cancellation.is_cancelled() {
+                    eprintln!("Warning: Interrupted; reporting the cycles that ran");
+                }
+                eprintln!("{}", render_monitor_report(&windows, cli.ascii_only, use_color(cli)));
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        if cancellation.is_cancelled() {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        return;
+    }
+
     let ping_str = if cli.pings == 1 { "request" } else { "requests" };
     
-    eprintln!("Pinging {} {} ({} {} each)...", cli.endpoints.len(), endpoint_str, cli.pings, ping_str);
+    let banner = if cli.cold_warm {
+        format!("Comparing cold and warm connections for {} {}...", cli.endpoints.len(), endpoint_str)
+    } else if cli.ab_connection {
+        format!("Alternating fresh and reused connections for {} {} ({} {} each)...", cli.endpoints.len(), endpoint_str, cli.pings, ping_str)
+    } else {
+        format!("Pinging {} {} ({} {} each)...", cli.endpoints.len(), endpoint_str, cli.pings, ping_str)
+    };
+    // With --json-to-stderr, stderr is for the JSON document, so the banner goes with the table
+    if !cli.quiet {
+        if cli.json_to_stderr {
+            println!("{}", banner);
+        } else {
+            eprintln!("{}", banner);
+        }
+    }
+
+    let config = &config;
+    let columns = if (cli.cold_warm || cli.ab_connection) && cli.columns.is_empty() {
+        vec![Column::Endpoint, Column::Status, Column::Cold, Column::Warm, Column::Handshake, Column::Block, Column::Error]
+    } else {
+        cli.columns.clone()
+    };
+    let render_options = RenderOptions {
+        columns,
+        width: cli.width,
+        compact_json: cli.compact_json,
+        leaderboard: cli.leaderboard,
+        summary: cli.summary,
+        block_distribution: cli.block_distribution,
+        convergence: cli.convergence,
+        ascii_only: cli.ascii_only,
+        color: use_color(&cli),
+        block_results: cli.method == PROBE_METHOD,
+        raw_block: cli.raw_block,
+        allowed_chain_ids: cli.allowed_chain_ids.clone(),
+        check_peers: cli.check_peers,
+        min_peers: cli.min_peers,
+        check_sync: cli.check_sync,
+        check_gas: cli.check_gas,
+    };
+
+    if let Some(every) = cli.watch {
+        watch(&cli, config, &labels, &render_options, Duration::from_secs(every)).await;
+        return;
+    }
+
+    // From here on the first Ctrl-C stops the pings but still reports what they found
+    let cancellation = cancel_on_ctrl_c();
+    let config = &PingConfig { cancel: Some(cancellation.clone()), ..config.clone() };
 
-    let ping_futures: Vec<_> = cli.endpoints
+    let started = Instant::now();
+    // The terminal gets --format, unless --output takes over without a separate --output-format
+    let print_to_stdout = cli.output.is_none() || cli.output_format.is_some();
+    // NDJSON goes out one line per endpoint as each finishes, instead of all at once at the end
+    let stream_ndjson = print_to_stdout && cli.format == OutputFormat::Ndjson;
+    // Machine-readable runs and redirected stderr have no use for a redrawn progress line
+    let show_progress = !cli.quiet
+        && !cli.json_to_stderr
+        && !matches!(cli.format, OutputFormat::Json | OutputFormat::Ndjson)
+        && std::io::stderr().is_terminal();
+    let mut progress = show_progress.then(|| Progress::start(cli.endpoints.len()));
+    let results = run_benchmark(&cli, config, &labels, |result| {
+        if let Some(progress) = progress.as_mut() {
+            progress.finished_one();
+        }
+        if stream_ndjson {
+            match render(OutputFormat::Ndjson, std::slice::from_ref(result), &render_options) {
+                Ok(line) => println!("{}", line),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+    })
+    .await;
+
+    if cancellation.is_cancelled() {
+        eprintln!("Warning: Interrupted; showing results for the pings that finished");
+    }
+
+    if print_to_stdout && !stream_ndjson {
+        match render(cli.format, &results, &render_options) {
+            Ok(rendered) => println!("{}", rendered),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    if cli.json_to_stderr {
+        match render(OutputFormat::Json, &results, &render_options) {
+            Ok(rendered) => eprintln!("{}", rendered),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    if let Some(path) = &cli.output {
+        let file_format = cli.output_format.unwrap_or(cli.format);
+        // Escape codes are only useful on a terminal, never in a file
+        let file_options = RenderOptions { color: false, ..render_options.clone() };
+        let written = render(file_format, &results, &file_options)
+            .and_then(|rendered| std::fs::write(path, rendered + "\n").map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            eprintln!("Error: Failed to write output to '{}': {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    // Like the banner, the summary stays off whichever stream carries the machine-readable output
+    if let Some(summary_format) = cli.summary_format {
+        let summary = RunSummary {
+            duration_ms: Some(started.elapsed().as_millis()),
+            ..RunSummary::from_results(&results, &render_options)
+        };
+        match render_summary(&summary, summary_format) {
+            Ok(rendered) if cli.json_to_stderr => println!("{}", rendered),
+            Ok(rendered) => eprintln!("{}", rendered),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    // Partial results can't pass or fail the fleet checks
+    if cancellation.is_cancelled() {
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+
+    if let Some(max_divergence) = cli.max_block_divergence
+        && let Err(message) = fleet::check_block_divergence(&results, max_divergence)
+    {
+        eprintln!("Error: {}", message);
+        std::process::exit(EXIT_CHECK_FAILED);
+    }
+
+    if cli.same_chain
+        && let Err(message) = fleet::check_same_chain(&results)
+    {
+        eprintln!("Error: {}", message);
+        std::process::exit(EXIT_CHECK_FAILED);
+    }
+
+    if let Err(message) = fleet::check_health(&results, cli.fail_on) {
+        eprintln!("Error: {}", message);
+        std::process::exit(EXIT_UNHEALTHY);
+    }
+}
+
+/// One full benchmark of every endpoint: cached or fresh pings, then tags, labels and fleet checks that
+/// mark up individual results, sorted fastest first.
+///
+/// `on_result` sees each result as soon as it's complete, in the order they finish. Fleet-wide
+/// markup such as `--max-lag` is only applied to the returned results.
+async fn run_benchmark(
+    cli: &Cli,
+    config: &PingConfig,
+    labels: &BTreeMap<String, String>,
+    mut on_result: impl FnMut(&PingResult),
+) -> Vec<PingResult> {
+    // With --cache-ttl, endpoints recently measured the same way are answered from disk instead of
+    // being pinged. The follow-up checks in `annotate` still ask them on every run.
+    let settings = measurement_settings(cli, config);
+    let mut cache = cli.cache_ttl.map(|_| ResultCache::load());
+    let tags: BTreeMap<String, String> = cli.tags.iter().cloned().collect();
+    // One client for the follow-up checks on every endpoint. If it can't be built the pings
+    // have failed the same way, and the checks are skipped.
+    let client = build_client(config).ok();
+    let mut results = Vec::new();
+    let mut to_ping = Vec::new();
+    for endpoint in &cli.endpoints {
+        let cached = cache.as_ref().zip(cli.cache_ttl)
+            .and_then(|(cache, ttl)| cache.get(endpoint, &settings, Duration::from_secs(ttl)));
+        match cached {
+            Some(mut result) => {
+                annotate(&mut result, client.as_ref(), cli, config, labels, &tags).await;
+                on_result(&result);
+                results.push(result);
+            }
+            None => to_ping.push(endpoint),
+        }
+    }
+
+    let (tags, client) = (&tags, client.as_ref());
+    let ping_futures: Vec<_> = to_ping
         .iter()
-        .map(|endpoint| ping_endpoint_multiple(endpoint, cli.pings,cli.timeout))
+        .map(|endpoint| async move {
+            let mut result = if cli.cold_warm {
+                ping_endpoint_cold_warm(endpoint, config).await
+            } else if cli.ab_connection {
+                ping_endpoint_alternating(endpoint, config).await
+            } else {
+                ping_endpoint_multiple(endpoint, config).await
+            };
+            // The cache keeps what was measured; markup is redone for every run that reads it
+            let measured = result.clone();
+            annotate(&mut result, client, cli, config, labels, tags).await;
+            (measured, result)
+        })
         .collect();
-    
-    let mut results = join_all(ping_futures).await;
 
-    // Sort results by average latency, fastest first. Failures go to the bottom.
-    results.sort_by_key(|r| r.avg_latency_ms.unwrap_or(u128::MAX));
+    // Futures don't start until polled, so --sequential can simply await them one by one.
+    // Otherwise up to --max-parallel run at once, and results are taken as they finish rather
+    // than all at once at the end.
+    let (mut measured, mut fresh_results) = (Vec::new(), Vec::new());
+    if cli.sequential {
+        for ping in ping_futures {
+            let (raw, result) = ping.await;
+            on_result(&result);
+            measured.push(raw);
+            fresh_results.push(result);
+        }
+    } else {
+        let mut pings = futures::stream::iter(ping_futures).buffer_unordered(cli.max_parallel.unwrap_or(usize::MAX));
+        while let Some((raw, result)) = pings.next().await {
+            on_result(&result);
+            measured.push(raw);
+            fresh_results.push(result);
+        }
+    }
+
+    if let Some(cache) = cache.as_mut() {
+        // An interrupted run's results stand for fewer pings than were asked for
+        for result in measured.iter().filter(|result| !result.cancelled) {
+            cache.insert(result, &settings);
+        }
+        if let Err(e) = cache.save() {
+            eprintln!("Warning: Failed to write result cache: {}", e);
+        }
+    }
+    results.extend(fresh_results);
+
+    for result in &results {
+        if let Some(content_type) = result.content_type.as_deref().filter(|ct| !is_json_content_type(ct)) {
+            eprintln!("Warning: {} responded with Content-Type '{}' instead of application/json", result.endpoint, content_type);
+        }
+    }
 
-    match cli.format.as_str() {
-    "json" => output_json(&results),
-    "table" => output_table(&results),
-    _ => eprintln!("Error: Unknown format '{}'. Use 'table' or 'json'.", cli.format),
+    if let Some(dir) = &cli.capture_extremes {
+        match save_extremes(dir, &results) {
+            Ok(0) => eprintln!("Warning: No successful pings to capture response bodies from"),
+            Ok(saved) if !cli.quiet => eprintln!("Saved {} fastest/slowest response bodies to '{}'", saved, dir.display()),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: Failed to save response bodies to '{}': {}", dir.display(), e),
+        }
     }
+
+    // Lag is relative to the highest head, so it has to wait for every endpoint
+    if let Some(max_lag) = cli.max_lag {
+        fleet::flag_lag(&mut results, max_lag);
+    }
+
+    sort_results(&mut results, cli.sort_by, cli.reverse, &cli.endpoints);
+    results
+
 }
 
-fn output_table(results: &[PingResult]) {
-    let mut table = Table::new();
+/// Every setting that changes what a measurement means, so a cached result only answers a run
+/// that would have measured the same thing. Markup like tags and the follow-up checks is redone
+/// on every run and doesn't count.
+fn measurement_settings(cli: &Cli, config: &PingConfig) -> String {
+    let mode = if cli.cold_warm { "cold-warm" } else if cli.ab_connection { "ab-connection" } else { "pings" };
+    serde_json::json!({
+        "method": config.method,
+        "params": config.params,
+        "mode": mode,
+        "pings": config.count,
+        "timeout_secs": config.timeout_secs,
+        "connect_timeout_secs": config.connect_timeout_secs,
+        "origin": config.origin,
+        "strict_content_type": config.strict_content_type,
+        "success_if": config.success_if.as_ref().map(|predicate| format!("{:?}", predicate)),
+        "max_response_bytes": config.max_response_bytes,
+        "local_address": config.local_address,
+        "ping_spacing_ms": config.ping_spacing.map(|spacing| spacing.as_millis()),
+        "interval_ms": config.interval.as_millis(),
+        "concurrency": config.concurrency,
+        "simulate_loss": config.simulate_loss.map(|loss| (loss.percent, loss.seed)),
+        "retries": config.retries,
+        "retry_backoff_ms": config.retry_backoff.as_millis(),
+        "headers": config.headers,
+        "proxy": config.proxy,
+        "accept_invalid_certs": config.accept_invalid_certs,
+        "id_start": config.id_start,
+        "warmup": config.warmup,
+        "batch": config.batch,
+        "user_agent": config.user_agent,
+        "probe_connect": config.probe_connect,
+        "capture_extremes": config.capture_extremes,
+    })
+    .to_string()
+}
 
-    table
-        .load_preset(UTF8_FULL)
-        .apply_modifier(UTF8_ROUND_CORNERS);
+/// Everything that marks up a single result on its own: tags and label, `--min-block-height`,
+/// and the checks that ask the endpoint over `client`
+async fn annotate(result: &mut PingResult, client: Option<&reqwest::Client>, cli: &Cli, config: &PingConfig, labels: &BTreeMap<String, String>, tags: &BTreeMap<String, String>) {
+    result.tags = tags.clone();
+    result.label = labels.get(&result.endpoint).cloned();
 
-    let multiple_pings = results.first().is_some_and(|r| r.ping_count > 1);
+    if let Some(min_height) = cli.min_block_height {
+        fleet::flag_below_min_height(std::slice::from_mut(result), min_height, cli.fail_below_min_height);
+    }
 
-    if multiple_pings {
-        // Mode A: Multiple Pings. We show "Avg Latency", "Min", and "Max".
-        table.set_header(vec!["Endpoint", "Status", "Avg Latency", "Min", "Max", "Success", "Block Number", "Last Error"]);
-    } else {
-        // Mode B: Single Ping. We show "Latency" and REMOVE "Min", "Max", and "Success" (Success count)
-        table.set_header(vec!["Endpoint", "Status", "Latency", "Block Number", "Last Error"]);
+    // After Ctrl-C nothing more is sent, so the follow-up checks are skipped
+    let Some(client) = client.filter(|_| !config.cancel.as_ref().is_some_and(Cancellation::is_cancelled)) else {
+        return;
+    };
+
+    if cli.same_chain || !cli.allowed_chain_ids.is_empty() {
+        result.chain_id = fetch_chain_id(client, &result.endpoint).await;
     }
 
-    for result in results {
-        let latency_value = result.avg_latency_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string());       
-        let success_count = format!("{}/{}", result.success_count, result.ping_count);
-        let block = result.block_number.as_deref().unwrap_or("-");
-        let error = result.error_message.as_deref().unwrap_or("-");        
-        let endpoint_display = if result.endpoint.len() > 50 {
-            format!("{}...", &result.endpoint[..47])
-        } else {
-            result.endpoint.clone()
-        };
+    if !cli.allowed_chain_ids.is_empty() {
+        fleet::enforce_allowed_chain_ids(std::slice::from_mut(result), &cli.allowed_chain_ids);
+    }
 
-        // Color Logic: We create a Cell and apply the color directly to it.
-        let status_cell = match result.status {
-            PingStatus::Success => Cell::new("SUCCESS").fg(Color::Green),
-            PingStatus::PartialSuccess => Cell::new("PARTIAL").fg(Color::Yellow),
-            PingStatus::Failure => Cell::new("FAILURE").fg(Color::Red),
-        };
+    if cli.check_peers {
+        result.peer_count = fetch_peer_count(client, &result.endpoint).await;
+    }
+    if cli.check_sync {
+        result.sync_status = fetch_sync_status(client, &result.endpoint).await;
+    }
+    if cli.check_gas {
+        result.gas_price_wei = fetch_gas_price(client, &result.endpoint).await;
+    }
+
+    if cli.downgrade_syncing {
+        fleet::downgrade_syncing(std::slice::from_mut(result));
+    }
+}
+
+/// A single stderr line counting finished endpoints, redrawn as each one completes and cleared
+/// after the last so later output starts on a clean line
+struct Progress {
+    finished: usize,
+    total: usize,
+}
+
+impl Progress {
+    fn start(total: usize) -> Self {
+        let progress = Progress { finished: 0, total };
+        progress.draw();
+        progress
+    }
 
-        let error_display = if error.len() > 40 {
-            format!("{}...", &error[..37])
+    fn finished_one(&mut self) {
+        self.finished += 1;
+        if self.finished < self.total {
+            self.draw();
         } else {
-            error.to_string()
+            let _ = crossterm::execute!(std::io::stderr(), Clear(ClearType::CurrentLine), MoveToColumn(0));
+        }
+    }
+
+    fn draw(&self) {
+        let _ = crossterm::execute!(std::io::stderr(), Clear(ClearType::CurrentLine), MoveToColumn(0));
+        eprint!("Finished {}/{} endpoints", self.finished, self.total);
+    }
+}
+
+/// Re-runs the benchmark every `every` until Ctrl-C. Tables redraw in place; other formats print
+/// each run after the last, with JSON compacted to one line per run.
+async fn watch(cli: &Cli, config: &PingConfig, labels: &BTreeMap<String, String>, options: &RenderOptions, every: Duration) {
+    let options = RenderOptions { compact_json: true, ..options.clone() };
+    let mut runs = 0;
+    loop {
+        let next_run = tokio::time::Instant::now() + every;
+        let results = tokio::select! {
+            results = run_benchmark(cli, config, labels, |_| {}) => results,
+            _ = tokio::signal::ctrl_c() => break,
         };
+        runs += 1;
 
-        // Add rows based on whether we are in multiple pings mode or not
-        if multiple_pings {
-            // Multiple pings mode: Show Avg, Min, Max
-            let min_latency = result.min_latency_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string());
-            let max_latency = result.max_latency_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string());
-            
-            table.add_row(vec![
-                Cell::new(&endpoint_display),
-                status_cell,
-                Cell::new(&latency_value),
-                Cell::new(&min_latency),
-                Cell::new(&max_latency),
-                Cell::new(&success_count),
-                Cell::new(block),
-                Cell::new(&error_display),
-            ]);
-        } else {
-            // Single ping mode: No Min, Max, and Success. And display Latency (instead if Avg Latency)
-            table.add_row(vec![
-                Cell::new(&endpoint_display),
-                status_cell,
-                Cell::new(&latency_value),
-                Cell::new(block),
-                Cell::new(&error_display),
-            ]);
+        match render(cli.format, &results, &options) {
+            Ok(rendered) if cli.format == OutputFormat::Table => {
+                let _ = crossterm::execute!(std::io::stdout(), Clear(ClearType::All), MoveTo(0, 0));
+                println!("Every {}s, run {} (Ctrl-C to stop)\n{}", every.as_secs(), runs, rendered);
+            }
+            Ok(rendered) => println!("{}", rendered),
+            Err(e) => eprintln!("Error: {}", e),
         }
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_run) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    if !cli.quiet {
+        eprintln!("Stopped watching after {} {}", runs, if runs == 1 { "run" } else { "runs" });
     }
+}
 
-    println!("{table}");
+/// Whether any output of this run has the Connect column or `connect_ms`, the only use of the connect probe
+fn shows_connect_time(cli: &Cli) -> bool {
+    let shows = |format: OutputFormat| match format {
+        OutputFormat::Json | OutputFormat::Ndjson => true,
+        OutputFormat::Table if cli.leaderboard => false,
+        OutputFormat::Table if cli.columns.is_empty() => !(cli.cold_warm || cli.ab_connection),
+        OutputFormat::Table => cli.columns.contains(&Column::Connect),
+        _ => false,
+    };
+    // The daemon answers, and monitor cycles print, JSON whatever --format says
+    cli.daemon.is_some()
+        || cli.monitor.is_some()
+        || cli.json_to_stderr
+        || shows(cli.format)
+        || cli.output.is_some() && cli.output_format.is_some_and(shows)
 }
 
-fn output_json(results: &[chain_ping::PingResult]) {
-    if let Ok(json_string) = serde_json::to_string_pretty(results) {
-        println!("{}", json_string);
+/// A cancellation that the first Ctrl-C triggers; a second one quits straight away
+fn cancel_on_ctrl_c() -> Cancellation {
+    let cancellation = Cancellation::new();
+    let interrupt = cancellation.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            interrupt.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+        }
+    });
+    cancellation
+}
+
+/// Colors stay on unless `--no-color` or a non-empty `NO_COLOR` (see no-color.org) turns them off
+fn use_color(cli: &Cli) -> bool {
+    !cli.no_color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Splits `label=url` into its parts. Anything whose text before the first `=` has a `:` or `/`
+/// (such as `https://rpc.example/?key=abc`) is a plain URL.
+fn split_label(raw: &str) -> (Option<&str>, &str) {
+    match raw.split_once('=') {
+        Some((label, url)) if !label.is_empty() && !label.contains([':', '/']) => (Some(label), url),
+        _ => (None, raw),
+    }
+}
+
+/// One URL per line from `path`, or from stdin for `-`, skipping blank lines and `#` comments
+fn read_endpoints_file(path: &Path) -> std::io::Result<Vec<String>> {
+    let text = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
     } else {
-        eprintln!("Error: Failed to serialize results to JSON");
+        std::fs::read_to_string(path)?
+    };
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// A different seed per run: the clock's nanoseconds, which is plenty for picking test failures
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Writes `<endpoint_id>-fastest.json` and `<endpoint_id>-slowest.json` per endpoint, returning how many files were written
+fn save_extremes(dir: &Path, results: &[PingResult]) -> std::io::Result<usize> {
+    std::fs::create_dir_all(dir)?;
+    let mut saved = 0;
+    for result in results {
+        for (kind, captured) in [("fastest", &result.fastest_response), ("slowest", &result.slowest_response)] {
+            if let Some(captured) = captured {
+                std::fs::write(dir.join(format!("{}-{}.json", result.endpoint_id, kind)), &captured.body)?;
+                saved += 1;
+            }
+        }
     }
-}
\ No newline at end of file
+    Ok(saved)
+}
+
+/// Sorts results best first by `sort_by`, or worst first with `reverse`. Results without a value to
+/// sort on (failed endpoints, for latency, block and gas) always go to the bottom. Ties, and `SortBy::None`,
+/// keep the order of `input_order`.
+fn sort_results(results: &mut [PingResult], sort_by: SortBy, reverse: bool, input_order: &[String]) {
+    let position = |result: &PingResult| input_order.iter().position(|e| *e == result.endpoint).unwrap_or(usize::MAX);
+    results.sort_by_key(position);
+
+    // Lower is better. Exact averages, so endpoints that round to the same millisecond still rank correctly.
+    let key = |result: &PingResult| match sort_by {
+        SortBy::Latency => result.avg_latency_ms_exact,
+        SortBy::Success => (result.ping_count > 0).then(|| -(result.success_count as f64 / result.ping_count as f64)),
+        SortBy::Block => result.block_height.map(|height| -(height as f64)),
+        SortBy::Gas => result.gas_price_wei.map(|wei| wei as f64),
+        SortBy::None => Some(position(result) as f64),
+    };
+    results.sort_by(|a, b| match (key(a), key(b)) {
+        (Some(a), Some(b)) if reverse => b.total_cmp(&a),
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+}
@@ -0,0 +1,100 @@
+
+// --- IMPORTS ---
+
+use chain_ping::{build_client, ping_endpoint_with_client, PingConfig, PingResult};
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+
+// --- DATA STRUCTURES ---
+/// One endpoint's result for one cycle, as a line of the NDJSON stream
+#[derive(Serialize)]
+struct CycleLine<'a> {
+    cycle: usize,
+    /// When the cycle started, in milliseconds since the Unix epoch
+    timestamp_ms: u128,
+    #[serde(flatten)]
+    result: &'a PingResult,
+}
+
+/// Everything one endpoint did over the whole monitoring window
+#[derive(Debug, Default)]
+pub struct MonitorWindow {
+    pub endpoint: String,
+    pub cycles: usize,
+    pub pings: usize,
+    pub successes: usize,
+    /// Latency of every successful ping across all cycles, in fractional milliseconds
+    pub latencies: Vec<f64>,
+}
+
+
+// --- MONITORING ---
+/// Pings every endpoint once per `every` until `total` has passed, printing each cycle's results
+/// to stdout as NDJSON, and returns the per-endpoint totals for the final report.
+///
+/// Cycles start `every` apart (a slow cycle eats into the wait), and no cycle starts after `total`.
+/// One client is kept for the whole window, so connections stay warm between cycles. At most
+/// `max_parallel` endpoints are pinged at once, if set. Each result goes through `mark_up` before it's
+/// printed or counted.
+///
+/// When `config.cancel` fires, the cycle in flight is cut short and the totals so far are returned.
+pub async fn run<F, Fut>(
+    endpoints: &[String],
+    config: &PingConfig,
+    total: Duration,
+    every: Duration,
+    max_parallel: Option<usize>,
+    mark_up: F,
+) -> Result<Vec<MonitorWindow>, String>
+where
+    F: Fn(PingResult) -> Fut,
+    Fut: Future<Output = PingResult>,
+{
+    let client = build_client(config)?;
+    let mut windows: Vec<MonitorWindow> = endpoints
+        .iter()
+        .map(|endpoint| MonitorWindow { endpoint: endpoint.clone(), ..MonitorWindow::default() })
+        .collect();
+
+    let started = Instant::now();
+    let mut cycle = 0;
+    while started.elapsed() < total {
+        cycle += 1;
+        let cycle_start = Instant::now();
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+
+        let results: Vec<PingResult> = stream::iter(endpoints.iter().map(|endpoint| async {
+            mark_up(ping_endpoint_with_client(&client, endpoint, config).await).await
+        }))
+            .buffered(max_parallel.unwrap_or(usize::MAX))
+            .collect()
+            .await;
+        for (window, result) in windows.iter_mut().zip(&results) {
+            let line = CycleLine { cycle, timestamp_ms, result };
+            println!("{}", serde_json::to_string(&line).map_err(|_| "Failed to serialize results to JSON".to_string())?);
+
+            window.cycles += 1;
+            window.pings += result.ping_count;
+            window.successes += result.success_count;
+            window.latencies.extend(result.attempts.iter().filter_map(|a| a.latency_ms));
+        }
+
+        let next_cycle = cycle_start + every;
+        if next_cycle.duration_since(started) >= total {
+            break;
+        }
+        let wait = tokio::time::sleep_until(next_cycle.into());
+        match &config.cancel {
+            Some(cancel) => tokio::select! {
+                _ = wait => {}
+                _ = cancel.cancelled() => break,
+            },
+            None => wait.await,
+        }
+    }
+
+    Ok(windows)
+}
@@ -0,0 +1,1152 @@
+
+// --- IMPORTS ---
+
+use crate::fleet::{block_distribution, format_block_distribution, HeightCount};
+use crate::monitor::MonitorWindow;
+use chain_ping::{PingResult, PingStatus, SyncStatus};
+use comfy_table::{Table, presets::{ASCII_FULL, UTF8_FULL}, modifiers::UTF8_ROUND_CORNERS, Color, Cell, ContentArrangement};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use serde::Serialize;
+use std::str::FromStr;
+
+
+// --- DATA STRUCTURES ---
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    /// One compact JSON object per line, streamed as each endpoint finishes
+    Ndjson,
+    /// One row per endpoint, for spreadsheets and dataframes
+    Csv,
+    /// One row per individual ping, for plotting latency over time
+    CsvTimeseries,
+    /// JUnit XML with one test case per endpoint, for CI dashboards
+    Junit,
+    /// Prometheus text exposition format, for scraping into Grafana
+    Prometheus,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "csv-timeseries" => Ok(OutputFormat::CsvTimeseries),
+            "junit" => Ok(OutputFormat::Junit),
+            "prometheus" => Ok(OutputFormat::Prometheus),
+            _ => Err(format!("unknown format '{}'. Use 'table', 'json', 'ndjson', 'csv', 'csv-timeseries', 'junit' or 'prometheus'", s)),
+        }
+    }
+}
+
+/// What `--sort-by` orders the results by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortBy {
+    Latency,
+    Success,
+    Block,
+    /// Lowest gas price first, for `--check-gas`
+    Gas,
+    /// The order the endpoints were given in
+    None,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latency" => Ok(SortBy::Latency),
+            "success" => Ok(SortBy::Success),
+            "block" => Ok(SortBy::Block),
+            "gas" => Ok(SortBy::Gas),
+            "none" => Ok(SortBy::None),
+            _ => Err(format!("unknown sort key '{}'. Use 'latency', 'success', 'block', 'gas' or 'none'", s)),
+        }
+    }
+}
+
+/// How `--summary-format` renders the run summary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummaryFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for SummaryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(SummaryFormat::Text),
+            "json" => Ok(SummaryFormat::Json),
+            _ => Err(format!("unknown summary format '{}'. Use 'text' or 'json'", s)),
+        }
+    }
+}
+
+/// A column that can be selected for the table with `--columns`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Endpoint,
+    Status,
+    Avg,
+    Min,
+    Max,
+    Success,
+    Block,
+    Error,
+    Cold,
+    Warm,
+    Handshake,
+    Parse,
+    Ci,
+    Errors,
+    Chain,
+    Lag,
+    Jitter,
+    Connect,
+    Median,
+    Peers,
+    Synced,
+    Gas,
+    PerCall,
+    Size,
+}
+
+impl Column {
+    /// Every column with the name it is selected by, in default display order
+    const ALL: [(&'static str, Column); 24] = [
+        ("endpoint", Column::Endpoint),
+        ("status", Column::Status),
+        ("avg", Column::Avg),
+        ("median", Column::Median),
+        ("min", Column::Min),
+        ("max", Column::Max),
+        ("jitter", Column::Jitter),
+        ("connect", Column::Connect),
+        ("success", Column::Success),
+        ("block", Column::Block),
+        ("error", Column::Error),
+        ("cold", Column::Cold),
+        ("warm", Column::Warm),
+        ("handshake", Column::Handshake),
+        ("parse", Column::Parse),
+        ("ci", Column::Ci),
+        ("errors", Column::Errors),
+        ("chain", Column::Chain),
+        ("lag", Column::Lag),
+        ("peers", Column::Peers),
+        ("synced", Column::Synced),
+        ("gas", Column::Gas),
+        ("per-call", Column::PerCall),
+        ("size", Column::Size),
+    ];
+
+    /// `block_results` is false when a custom `--method` makes the result something other than a block number
+    fn header(self, multiple_pings: bool, block_results: bool) -> &'static str {
+        match self {
+            Column::Endpoint => "Endpoint",
+            Column::Status => "Status",
+            Column::Avg if multiple_pings => "Avg Latency",
+            Column::Avg => "Latency",
+            Column::Min => "Min",
+            Column::Max => "Max",
+            Column::Success => "Success",
+            Column::Block if block_results => "Block Number",
+            Column::Block => "Result",
+            Column::Error => "Last Error",
+            Column::Cold => "Cold",
+            Column::Warm => "Warm",
+            Column::Handshake => "Handshake",
+            Column::Parse => "Parse",
+            Column::Ci => "95% CI",
+            Column::Errors => "Errors",
+            Column::Chain => "Chain",
+            Column::Lag => "Lag",
+            Column::Jitter => "Jitter",
+            Column::Connect => "Connect",
+            Column::Median => "Median",
+            Column::Peers => "Peers",
+            Column::Synced => "Synced",
+            Column::Gas => "Gas Price",
+            Column::PerCall => "Per Call",
+            Column::Size => "Size",
+        }
+    }
+
+    fn cell(self, result: &PingResult, options: &RenderOptions) -> Cell {
+        match self {
+            Column::Endpoint => {
+                let endpoint_display = if let Some(label) = &result.label {
+                    label.clone()
+                } else if result.endpoint.len() > 50 {
+                    format!("{}...", &result.endpoint[..47])
+                } else {
+                    result.endpoint.clone()
+                };
+                let cell = Cell::new(endpoint_display);
+                if result.lagging { cell.fg(Color::Yellow) } else { cell }
+            }
+            Column::Status => {
+                // Color Logic: We create a Cell and apply the color directly to it.
+                let status_color = match result.status {
+                    PingStatus::Success => Color::Green,
+                    PingStatus::PartialSuccess => Color::Yellow,
+                    PingStatus::Failure => Color::Red,
+                };
+                let mut status_text = status_label(result.status).to_string();
+                if result.from_cache {
+                    status_text.push_str(" (cached)");
+                }
+                if result.cancelled {
+                    status_text.push_str(" (cancelled)");
+                }
+                if result.below_min_height {
+                    status_text.push_str(" (suspect)");
+                }
+                Cell::new(status_text).fg(status_color)
+            }
+            Column::Avg => Cell::new(format_latency(result.avg_latency_ms, result.avg_latency_ms_exact)),
+            Column::Min => Cell::new(format_latency(result.min_latency_ms, result.min_latency_ms_exact)),
+            Column::Max => Cell::new(format_latency(result.max_latency_ms, result.max_latency_ms_exact)),
+            Column::Median => Cell::new(format_latency(result.median_latency_ms, result.median_latency_ms_exact)),
+            Column::Jitter => Cell::new(format_latency(result.stddev_latency_ms, result.stddev_latency_ms_exact)),
+            Column::Connect => Cell::new(format_latency(result.connect_ms.map(|ms| ms.round() as u128), result.connect_ms)),
+            Column::Success => Cell::new(format!("{}/{}", result.success_count, result.ping_count)),
+            Column::Block => Cell::new(match (result.block_height, result.result.as_deref()) {
+                // Decimal heights are what people compare by eye; --raw-block keeps the hex
+                (Some(height), _) if !options.raw_block => height.to_string(),
+                // Results of heavier methods can be huge; the table only needs a glimpse
+                (_, Some(value)) if value.chars().count() > 50 => format!("{}...", value.chars().take(47).collect::<String>()),
+                (_, Some(value)) => value.to_string(),
+                (_, None) => "-".to_string(),
+            }),
+            Column::Error => {
+                // Failures of several kinds say more as a breakdown than as whichever came last
+                let breakdown = (result.error_counts.len() > 1).then(|| format_error_counts(&result.error_counts));
+                let error = breakdown.as_deref().or(result.error_message.as_deref()).unwrap_or("-");
+                let error_display = if error.len() > 40 {
+                    format!("{}...", &error[..37])
+                } else {
+                    error.to_string()
+                };
+                Cell::new(error_display)
+            }
+            Column::Cold => Cell::new(format_ms(result.cold_latency_ms)),
+            Column::Warm => Cell::new(format_ms(result.warm_latency_ms)),
+            Column::Handshake => Cell::new(result.handshake_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string())),
+            Column::Parse => Cell::new(result.avg_parse_ms.map(|ms| format!("{:.3}ms", ms)).unwrap_or_else(|| "-".to_string())),
+            Column::Errors => Cell::new(match result.distinct_errors.len() {
+                0 => "-".to_string(),
+                1 => "1 error".to_string(),
+                n => format!("{} errors", n),
+            }),
+            Column::Ci => Cell::new(result.avg_latency_ci_ms.map(|(low, high)| format!("{}-{}ms", low, high)).unwrap_or_else(|| "-".to_string())),
+            Column::Chain => match result.chain_id {
+                Some(id) if !options.allowed_chain_ids.is_empty() && !options.allowed_chain_ids.contains(&id) => Cell::new(id).fg(Color::Red),
+                Some(id) => Cell::new(id),
+                None => Cell::new("-"),
+            },
+            Column::Lag => {
+                let cell = Cell::new(result.blocks_behind.map(|behind| behind.to_string()).unwrap_or_else(|| "-".to_string()));
+                if result.lagging { cell.fg(Color::Yellow) } else { cell }
+            }
+            Column::Peers => match result.peer_count {
+                Some(peers) if options.min_peers.is_some_and(|min| peers < min) => Cell::new(peers).fg(Color::Yellow),
+                Some(peers) => Cell::new(peers),
+                None => Cell::new("-"),
+            },
+            Column::Synced => match result.sync_status {
+                Some(SyncStatus::Synced) => Cell::new("yes"),
+                Some(SyncStatus::Syncing) => Cell::new("syncing").fg(Color::Yellow),
+                None => Cell::new("-"),
+            },
+            Column::Gas => Cell::new(result.gas_price_wei.map(format_gwei).unwrap_or_else(|| "-".to_string())),
+            Column::PerCall => Cell::new(format_latency(result.per_call_latency_ms.map(|ms| ms.round() as u128), result.per_call_latency_ms)),
+            Column::Size => Cell::new(result.avg_response_bytes.map(format_bytes).unwrap_or_else(|| "-".to_string())),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Column::ALL
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = Column::ALL.iter().map(|(name, _)| *name).collect();
+                format!("unknown column '{}'. Valid columns: {}", s, valid.join(", "))
+            })
+    }
+}
+
+/// Presentation settings for the renderers
+#[derive(Clone)]
+pub struct RenderOptions {
+    /// Columns to show, in order. Empty means the default set for the ping mode.
+    pub columns: Vec<Column>,
+    /// Fixed table width in characters. Without it the terminal width is used when stdout is a TTY.
+    pub width: Option<u16>,
+    /// Emit JSON on a single line instead of pretty-printing it
+    pub compact_json: bool,
+    /// Render the table as a ranked leaderboard instead
+    pub leaderboard: bool,
+    /// Wrap JSON output as `{"summary": ..., "results": [...]}`
+    pub summary: bool,
+    /// Show how many endpoints sit at each block height (after the table, and in the JSON summary)
+    pub block_distribution: bool,
+    /// Show how the p95 estimate settles as samples accumulate, and whether it's stable within this percentage
+    pub convergence: Option<f64>,
+    /// Plain ASCII borders and markers, and no colors
+    pub ascii_only: bool,
+    /// Color status cells and warnings; off for `--no-color`, `NO_COLOR` and files.
+    /// Even when on, comfy-table leaves colors out unless stdout is a terminal.
+    pub color: bool,
+    /// Results are block numbers from the default method, rather than some other method's output
+    pub block_results: bool,
+    /// Show block numbers as the hex the endpoint returned instead of decimal
+    pub raw_block: bool,
+    /// Chains endpoints are expected to be on; any other chain id is shown in red
+    pub allowed_chain_ids: Vec<u64>,
+    /// Show the Peers column, for `--check-peers`
+    pub check_peers: bool,
+    /// Peer counts below this are shown in yellow
+    pub min_peers: Option<u64>,
+    /// Show the Synced column, for `--check-sync`
+    pub check_sync: bool,
+    /// Show the Gas Price column, for `--check-gas`
+    pub check_gas: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            columns: vec![],
+            width: None,
+            compact_json: false,
+            leaderboard: false,
+            summary: false,
+            block_distribution: false,
+            convergence: None,
+            ascii_only: false,
+            color: true,
+            block_results: true,
+            raw_block: false,
+            allowed_chain_ids: vec![],
+            check_peers: false,
+            min_peers: None,
+            check_sync: false,
+            check_gas: false,
+        }
+    }
+}
+
+/// Per-status rollup of a run, so JSON consumers don't have to count results themselves
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub total_endpoints: usize,
+    pub success_count: usize,
+    pub partial_count: usize,
+    pub failure_count: usize,
+    /// Endpoint with the lowest average latency among those that answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fastest_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fastest_avg_latency_ms: Option<f64>,
+    /// Endpoint with the highest average latency among those that answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slowest_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slowest_avg_latency_ms: Option<f64>,
+    /// Wall-clock time of the whole run, when the caller measured it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_heights: Option<Vec<HeightCount>>,
+}
+
+impl RunSummary {
+    pub fn from_results(results: &[PingResult], options: &RenderOptions) -> Self {
+        let count = |status: PingStatus| results.iter().filter(|r| r.status == status).count();
+        // Failed endpoints have no average, so only those that answered are compared
+        let answered = || results.iter().filter_map(|r| r.avg_latency_ms_exact.map(|avg| (avg, r)));
+        let fastest = answered().min_by(|(a, _), (b, _)| a.total_cmp(b));
+        let slowest = answered().max_by(|(a, _), (b, _)| a.total_cmp(b));
+        RunSummary {
+            total_endpoints: results.len(),
+            success_count: count(PingStatus::Success),
+            partial_count: count(PingStatus::PartialSuccess),
+            failure_count: count(PingStatus::Failure),
+            fastest_endpoint: fastest.map(|(_, r)| r.endpoint.clone()),
+            fastest_avg_latency_ms: fastest.map(|(avg, _)| avg),
+            slowest_endpoint: slowest.map(|(_, r)| r.endpoint.clone()),
+            slowest_avg_latency_ms: slowest.map(|(avg, _)| avg),
+            duration_ms: None,
+            block_heights: options.block_distribution.then(|| block_distribution(results)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    summary: RunSummary,
+    results: &'a [PingResult],
+}
+
+
+// --- RENDERING ---
+pub fn render(format: OutputFormat, results: &[PingResult], options: &RenderOptions) -> Result<String, String> {
+    match format {
+        OutputFormat::Table => {
+            let mut output = if options.leaderboard { render_leaderboard(results, options) } else { render_table(results, options) };
+            let distribution = block_distribution(results);
+            if options.block_distribution && !distribution.is_empty() {
+                output = format!("{}\n{}", output, format_block_distribution(&distribution));
+            }
+            if let Some(tolerance) = options.convergence {
+                output = format!("{}\n\n{}", output, render_convergence(results, tolerance, options.ascii_only, options.color));
+            }
+            if options.summary {
+                let summary = render_summary(&RunSummary::from_results(results, options), SummaryFormat::Text)?;
+                output = format!("{}\n{}", output, summary);
+            }
+            Ok(output)
+        }
+        OutputFormat::Json => render_json(results, options),
+        OutputFormat::Ndjson => render_ndjson(results),
+        OutputFormat::Csv => Ok(render_csv(results)),
+        OutputFormat::CsvTimeseries => Ok(render_csv_timeseries(results)),
+        OutputFormat::Junit => Ok(render_junit(results)),
+        OutputFormat::Prometheus => Ok(output_prometheus(results)),
+    }
+}
+
+fn render_table(results: &[PingResult], options: &RenderOptions) -> String {
+    let multiple_pings = results.first().is_some_and(|r| r.ping_count > 1);
+
+    let explicit_columns = !options.columns.is_empty();
+    let mut columns = if explicit_columns {
+        options.columns.clone()
+    } else if multiple_pings {
+        // Mode A: Multiple Pings. We show "Avg Latency", "Min", and "Max".
+        default_columns(true)
+    } else {
+        // Mode B: Single Ping. We show "Latency" and REMOVE "Min", "Max", and "Success" (Success count)
+        default_columns(false)
+    };
+    // Chain ids are only fetched when a chain check asked for them, so show them whenever they're known
+    if !explicit_columns && results.iter().any(|r| r.chain_id.is_some()) {
+        columns.insert(columns.iter().position(|c| *c == Column::Status).map_or(0, |i| i + 1), Column::Chain);
+    }
+    // Likewise lag, which is only worked out for --max-lag
+    if !explicit_columns && results.iter().any(|r| r.blocks_behind.is_some()) {
+        columns.insert(columns.iter().position(|c| *c == Column::Block).map_or(columns.len(), |i| i + 1), Column::Lag);
+    }
+    // Unlike the chain, peers get a column even when no endpoint supports net_peerCount
+    if !explicit_columns && options.check_peers {
+        columns.insert(columns.iter().position(|c| *c == Column::Block).map_or(columns.len(), |i| i + 1), Column::Peers);
+    }
+    // Batched latencies are per round trip, so show what that works out to per call
+    if !explicit_columns && results.iter().any(|r| r.batch_size.is_some()) {
+        columns.insert(columns.iter().position(|c| *c == Column::Avg).map_or(columns.len(), |i| i + 1), Column::PerCall);
+    }
+    // Block number answers are a few dozen bytes; sizes only say something for bigger methods like eth_getLogs
+    if !explicit_columns && results.iter().any(|r| r.avg_response_bytes.is_some_and(|bytes| bytes >= SIZE_COLUMN_THRESHOLD)) {
+        columns.insert(columns.iter().position(|c| *c == Column::Block).map_or(columns.len(), |i| i + 1), Column::Size);
+    }
+    if !explicit_columns && options.check_gas {
+        columns.insert(columns.iter().position(|c| *c == Column::Block).map_or(columns.len(), |i| i + 1), Column::Gas);
+    }
+    if !explicit_columns && options.check_sync {
+        columns.insert(columns.iter().position(|c| *c == Column::Status).map_or(0, |i| i + 1), Column::Synced);
+    }
+
+    let mut table = build_table(results, &columns, multiple_pings, options);
+
+    // On a narrow screen, drop the least important default columns before resorting to wrapping.
+    // Columns picked explicitly with --columns are always kept.
+    if let (false, Some(target)) = (explicit_columns, table.width()) {
+        for droppable in DROP_ORDER {
+            if natural_width(results, &columns, multiple_pings, options) <= target as usize {
+                break;
+            }
+            columns.retain(|c| *c != droppable);
+        }
+        table = build_table(results, &columns, multiple_pings, options);
+    }
+
+    table.to_string()
+}
+
+/// Ranks healthy endpoints in their sorted order, with the gap to first place. Failures are listed below.
+fn render_leaderboard(results: &[PingResult], options: &RenderOptions) -> String {
+    let (mut ranked, unranked): (Vec<&PingResult>, Vec<&PingResult>) = results
+        .iter()
+        .partition(|r| r.status != PingStatus::Failure && r.avg_latency_ms_exact.is_some());
+    // Ranked by latency whatever --sort-by and --reverse did to the results, so gaps count up from the fastest
+    ranked.sort_by(|a, b| a.avg_latency_ms_exact.unwrap_or(f64::MAX).total_cmp(&b.avg_latency_ms_exact.unwrap_or(f64::MAX)));
+
+    let mut table = new_table(options.ascii_only, options.color, options.width);
+    table.set_header(vec!["Rank", "Endpoint", "Avg Latency", "Gap"]);
+
+    let leader = ranked.first().and_then(|r| r.avg_latency_ms_exact);
+    for (position, result) in ranked.iter().enumerate() {
+        let rank = match position {
+            _ if options.ascii_only => (position + 1).to_string(),
+            0 => "🥇 1".to_string(),
+            1 => "🥈 2".to_string(),
+            2 => "🥉 3".to_string(),
+            _ => (position + 1).to_string(),
+        };
+        let gap = match (position, leader, result.avg_latency_ms_exact) {
+            (0, _, _) => "-".to_string(),
+            (_, Some(leader), Some(avg)) => format_gap(avg - leader),
+            _ => "-".to_string(),
+        };
+        table.add_row(vec![
+            Cell::new(rank),
+            Column::Endpoint.cell(result, options),
+            Column::Avg.cell(result, options),
+            Cell::new(gap),
+        ]);
+    }
+
+    let mut rendered = table.to_string();
+    if !unranked.is_empty() {
+        rendered.push_str("\n\nNot ranked:");
+        for result in unranked {
+            let error = result.error_message.as_deref().unwrap_or("no successful pings");
+            rendered.push_str(&format!("\n  {} ({})", display_name(result), error));
+        }
+    }
+    rendered
+}
+
+/// Running p95 per endpoint at doubling sample counts, so users can tell whether `--pings` was enough
+fn render_convergence(results: &[PingResult], tolerance_pct: f64, ascii_only: bool, color: bool) -> String {
+    let mut table = new_table(ascii_only, color, None);
+    table.set_header(vec!["Endpoint", "Samples", "p95", "Change"]);
+
+    let mut verdicts = Vec::new();
+    for result in results {
+        let samples: Vec<f64> = result.attempts.iter().filter_map(|a| a.latency_ms).collect();
+        let estimates: Vec<(usize, f64)> = convergence_points(samples.len())
+            .into_iter()
+            .map(|n| (n, p95(&samples[..n])))
+            .collect();
+
+        let mut previous = None;
+        let mut last_change = None;
+        for (n, estimate) in &estimates {
+            let change = previous.map(|prev: f64| (estimate - prev) / prev * 100.0);
+            table.add_row(vec![
+                Cell::new(display_name(result)),
+                Cell::new(n),
+                Cell::new(format_latency(Some(estimate.round() as u128), Some(*estimate))),
+                Cell::new(change.map(|c| format!("{:+.1}%", c)).unwrap_or_else(|| "-".to_string())),
+            ]);
+            previous = Some(*estimate);
+            last_change = change;
+        }
+
+        let verdict = match last_change {
+            None => "too few successful pings to judge".to_string(),
+            Some(change) if change.abs() <= tolerance_pct => format!("stable within {}%", tolerance_pct),
+            Some(change) => format!("still moving ({:+.1}%), try more --pings", change),
+        };
+        verdicts.push(format!("  {}: {}", display_name(result), verdict));
+    }
+
+    format!("p95 convergence:\n{}\n{}", table, verdicts.join("\n"))
+}
+
+/// Sample counts to estimate at: 5, 10, 20, 40, ... and finally every sample
+fn convergence_points(total: usize) -> Vec<usize> {
+    let mut points: Vec<usize> = std::iter::successors(Some(5), |n| Some(n * 2)).take_while(|n| *n < total).collect();
+    if total > 0 {
+        points.push(total);
+    }
+    points
+}
+
+/// Nearest-rank 95th percentile
+fn p95(samples: &[f64]) -> f64 {
+    percentile(samples, 0.95)
+}
+
+/// Nearest-rank percentile, with `fraction` between 0 and 1
+fn percentile(samples: &[f64], fraction: f64) -> f64 {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = (sorted.len() as f64 * fraction).ceil() as usize;
+    sorted[rank.saturating_sub(1)]
+}
+
+/// The end-of-window report for `--monitor`: availability and latency percentiles over every cycle
+pub fn render_monitor_report(windows: &[MonitorWindow], ascii_only: bool, color: bool) -> String {
+    let mut table = new_table(ascii_only, color, None);
+    table.set_header(vec!["Endpoint", "Cycles", "Availability", "p50", "p95", "p99"]);
+    for window in windows {
+        let availability = if window.pings == 0 { 0.0 } else { window.successes as f64 * 100.0 / window.pings as f64 };
+        let latency = |fraction: f64| {
+            if window.latencies.is_empty() {
+                "-".to_string()
+            } else {
+                let ms = percentile(&window.latencies, fraction);
+                format_latency(Some(ms.round() as u128), Some(ms))
+            }
+        };
+        table.add_row(vec![
+            window.endpoint.clone(),
+            window.cycles.to_string(),
+            format!("{:.1}% ({}/{})", availability, window.successes, window.pings),
+            latency(0.50),
+            latency(0.95),
+            latency(0.99),
+        ]);
+    }
+    table.to_string()
+}
+
+fn format_gap(gap_ms: f64) -> String {
+    if gap_ms < 10.0 {
+        format!("+{:.2}ms", gap_ms)
+    } else {
+        format!("+{:.0}ms", gap_ms)
+    }
+}
+
+/// Smallest average response, in bytes, that makes the table show a Size column
+const SIZE_COLUMN_THRESHOLD: usize = 1024;
+
+/// Default columns in the order they are given up when the table doesn't fit
+const DROP_ORDER: [Column; 8] = [Column::Connect, Column::Jitter, Column::Min, Column::Max, Column::Median, Column::Success, Column::Block, Column::Error];
+
+/// Every table starts here, so `--ascii-only` and `--no-color` switch borders and colors in one place
+fn new_table(ascii_only: bool, color: bool, width: Option<u16>) -> Table {
+    let mut table = Table::new();
+    if ascii_only {
+        table.load_preset(ASCII_FULL);
+    } else {
+        table.load_preset(UTF8_FULL).apply_modifier(UTF8_ROUND_CORNERS);
+    }
+
+    if ascii_only || !color {
+        // comfy-table only drops colors when it thinks there's no TTY, and then stops looking up the width itself
+        table.force_no_tty();
+        if let Some(width) = width.or_else(terminal_width) {
+            table.set_width(width);
+        }
+    } else if let Some(width) = width {
+        table.set_width(width);
+    }
+
+    // Dynamic arrangement wraps cell contents so the table fits the terminal instead of overflowing
+    table.set_content_arrangement(ContentArrangement::Dynamic);
+    table
+}
+
+fn terminal_width() -> Option<u16> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    crossterm::terminal::size().ok().map(|(width, _)| width).filter(|width| *width > 0)
+}
+
+fn build_table(results: &[PingResult], columns: &[Column], multiple_pings: bool, options: &RenderOptions) -> Table {
+    let mut table = new_table(options.ascii_only, options.color, options.width);
+
+    table.set_header(columns.iter().map(|c| c.header(multiple_pings, options.block_results)).collect::<Vec<_>>());
+
+    for result in results {
+        table.add_row(columns.iter().map(|c| c.cell(result, options)).collect::<Vec<_>>());
+    }
+
+    table
+}
+
+/// Width the table would take if nothing were wrapped, measured on its top border
+fn natural_width(results: &[PingResult], columns: &[Column], multiple_pings: bool, options: &RenderOptions) -> usize {
+    let mut table = build_table(results, columns, multiple_pings, &RenderOptions { width: None, ..options.clone() });
+    table.set_content_arrangement(ContentArrangement::Disabled);
+    table.lines().next().map(|line| line.chars().count()).unwrap_or(0)
+}
+
+fn default_columns(multiple_pings: bool) -> Vec<Column> {
+    if multiple_pings {
+        vec![Column::Endpoint, Column::Status, Column::Avg, Column::Median, Column::Connect, Column::Min, Column::Max, Column::Jitter, Column::Success, Column::Block, Column::Error]
+    } else {
+        vec![Column::Endpoint, Column::Status, Column::Avg, Column::Connect, Column::Block, Column::Error]
+    }
+}
+
+/// Most frequent first, e.g. `3x timeout, 2x http 429`
+fn format_error_counts(counts: &BTreeMap<String, usize>) -> String {
+    let mut counts: Vec<(&String, &usize)> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1));
+    counts.iter().map(|(kind, count)| format!("{}x {}", count, kind)).collect::<Vec<_>>().join(", ")
+}
+
+fn format_ms(ms: Option<u128>) -> String {
+    ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string())
+}
+
+/// Whole (rounded) milliseconds, except below 10ms where the fractional part is what tells endpoints apart
+fn format_latency(rounded: Option<u128>, exact: Option<f64>) -> String {
+    match exact {
+        Some(exact) if exact < 10.0 => format!("{:.2}ms", exact),
+        _ => format_ms(rounded),
+    }
+}
+
+/// Response sizes in bytes, KB or MB (of 1024)
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Gas prices in gwei, with more decimals below 1 gwei where L2s tend to sit
+fn format_gwei(wei: u128) -> String {
+    let gwei = wei as f64 / 1e9;
+    if gwei < 1.0 { format!("{:.4} gwei", gwei) } else { format!("{:.2} gwei", gwei) }
+}
+
+/// Renders the run summary on its own, independently of the per-endpoint output format
+pub fn render_summary(summary: &RunSummary, format: SummaryFormat) -> Result<String, String> {
+    match format {
+        SummaryFormat::Json => to_json(summary, true).map_err(|_| "Failed to serialize summary to JSON".to_string()),
+        SummaryFormat::Text => {
+            let noun = if summary.total_endpoints == 1 { "endpoint" } else { "endpoints" };
+            let mut line = format!(
+                "{} {}: {} succeeded, {} partial, {} failed",
+                summary.total_endpoints, noun, summary.success_count, summary.partial_count, summary.failure_count
+            );
+            if let (Some(fastest), Some(avg)) = (&summary.fastest_endpoint, summary.fastest_avg_latency_ms) {
+                line.push_str(&format!(". Fastest: {} ({})", fastest, format_latency(Some(avg.round() as u128), Some(avg))));
+            }
+            if let (Some(slowest), Some(avg)) = (&summary.slowest_endpoint, summary.slowest_avg_latency_ms) {
+                line.push_str(&format!(", slowest: {} ({})", slowest, format_latency(Some(avg.round() as u128), Some(avg))));
+            }
+            if let Some(duration_ms) = summary.duration_ms {
+                line.push_str(&format!(". Took {:.2}s", duration_ms as f64 / 1000.0));
+            }
+            Ok(line)
+        }
+    }
+}
+
+fn render_json(results: &[PingResult], options: &RenderOptions) -> Result<String, String> {
+    // The block height tally only has a place in the summary, so asking for it brings the summary along
+    let json = if options.summary || options.block_distribution {
+        let report = JsonReport { summary: RunSummary::from_results(results, options), results };
+        to_json(&report, options.compact_json)
+    } else {
+        to_json(&results, options.compact_json)
+    };
+    json.map_err(|_| "Failed to serialize results to JSON".to_string())
+}
+
+/// Each result on its own line as standalone JSON, with no surrounding array
+fn render_ndjson(results: &[PingResult]) -> Result<String, String> {
+    let lines: Result<Vec<String>, _> = results.iter().map(serde_json::to_string).collect();
+    lines.map(|lines| lines.join("\n")).map_err(|_| "Failed to serialize results to JSON".to_string())
+}
+
+fn to_json<T: Serialize>(value: &T, compact: bool) -> serde_json::Result<String> {
+    if compact {
+        serde_json::to_string(value)
+    } else {
+        serde_json::to_string_pretty(value)
+    }
+}
+
+/// Latencies are exact milliseconds; cells with no value are left empty, and string results lose their JSON quotes
+fn render_csv(results: &[PingResult]) -> String {
+    let exact = |ms: Option<f64>| ms.map(|ms| format!("{:.3}", ms)).unwrap_or_default();
+    let mut lines = vec!["endpoint,status,avg_latency_ms,min_latency_ms,max_latency_ms,success_count,ping_count,result,error".to_string()];
+    for result in results {
+        lines.push(format!(
+            "{},{},{},{},{},{},{},{},{}",
+            csv_field(&result.endpoint),
+            status_label(result.status),
+            exact(result.avg_latency_ms_exact),
+            exact(result.min_latency_ms_exact),
+            exact(result.max_latency_ms_exact),
+            result.success_count,
+            result.ping_count,
+            csv_field(&result.result.as_deref().map(unquote_json_string).unwrap_or_default()),
+            csv_field(result.error_message.as_deref().unwrap_or_default()),
+        ));
+    }
+    lines.join("\n")
+}
+
+fn render_csv_timeseries(results: &[PingResult]) -> String {
+    let mut lines = vec!["endpoint,attempt,timestamp_ms,latency_ms,success".to_string()];
+    for result in results {
+        for attempt in &result.attempts {
+            lines.push(format!(
+                "{},{},{},{},{}",
+                csv_field(&result.endpoint),
+                attempt.index,
+                attempt.timestamp_ms,
+                attempt.latency_ms.map(|ms| format!("{:.3}", ms)).unwrap_or_default(),
+                attempt.success,
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Successful endpoints pass, failed ones fail, and partial successes are reported as skipped
+fn render_junit(results: &[PingResult]) -> String {
+    let count = |status: PingStatus| results.iter().filter(|r| r.status == status).count();
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        format!(
+            r#"<testsuite name="chain-ping" tests="{}" failures="{}" skipped="{}">"#,
+            results.len(),
+            count(PingStatus::Failure),
+            count(PingStatus::PartialSuccess),
+        ),
+    ];
+
+    for result in results {
+        let time = result.avg_latency_ms_exact.map(|ms| ms / 1000.0).unwrap_or(0.0);
+        lines.push(format!(
+            r#"  <testcase classname="chain-ping" name="{}" time="{:.6}">"#,
+            xml_escape(&result.endpoint),
+            time,
+        ));
+        lines.push("    <properties>".to_string());
+        let properties = [
+            ("avg_latency_ms", result.avg_latency_ms_exact.map(|ms| format!("{:.3}", ms))),
+            ("success_count", Some(format!("{}/{}", result.success_count, result.ping_count))),
+            ("result", result.result.as_deref().map(|b| b.trim_matches('"').to_string())),
+        ];
+        for (name, value) in properties {
+            if let Some(value) = value {
+                lines.push(format!(r#"      <property name="{}" value="{}"/>"#, name, xml_escape(&value)));
+            }
+        }
+        lines.push("    </properties>".to_string());
+
+        let error = xml_escape(result.error_message.as_deref().unwrap_or("no successful pings"));
+        match result.status {
+            PingStatus::Success => {}
+            PingStatus::PartialSuccess => lines.push(format!(
+                r#"    <skipped message="{}/{} pings succeeded. Last error: {}"/>"#,
+                result.success_count, result.ping_count, error,
+            )),
+            PingStatus::Failure => lines.push(format!(r#"    <failure message="{0}">{0}</failure>"#, error)),
+        }
+        lines.push("  </testcase>".to_string());
+    }
+
+    lines.push("</testsuite>".to_string());
+    lines.join("\n")
+}
+
+/// Gauges grouped into metric families, each endpoint labelled by URL (plus its label and `--tag`s).
+/// Every endpoint gets `chain_ping_up`; the other series only appear once there's a value for them.
+fn output_prometheus(results: &[PingResult]) -> String {
+    let mut lines = Vec::new();
+    let mut family = |name: &str, help: &str, samples: Vec<(String, f64)>| {
+        if samples.is_empty() {
+            return;
+        }
+        lines.push(format!("# HELP {} {}", name, help));
+        lines.push(format!("# TYPE {} gauge", name));
+        for (labels, value) in samples {
+            lines.push(format!("{}{{{}}} {}", name, labels, value));
+        }
+    };
+
+    family(
+        "chain_ping_up",
+        "Whether any ping to the endpoint succeeded (1) or none did (0).",
+        results.iter().map(|r| (prometheus_labels(r, None), if r.success_count > 0 { 1.0 } else { 0.0 })).collect(),
+    );
+    family(
+        "chain_ping_latency_ms",
+        "Latency of the successful pings in milliseconds, by statistic.",
+        results
+            .iter()
+            .flat_map(|r| {
+                let stats = [
+                    ("avg", r.avg_latency_ms_exact),
+                    ("median", r.median_latency_ms_exact),
+                    ("min", r.min_latency_ms_exact),
+                    ("max", r.max_latency_ms_exact),
+                    ("stddev", r.stddev_latency_ms_exact),
+                ];
+                stats.into_iter().filter_map(move |(stat, ms)| ms.map(|ms| (prometheus_labels(r, Some(stat)), ms)))
+            })
+            .collect(),
+    );
+    family(
+        "chain_ping_success_ratio",
+        "Share of pings that succeeded, from 0 to 1.",
+        results
+            .iter()
+            .filter(|r| r.ping_count > 0)
+            .map(|r| (prometheus_labels(r, None), r.success_count as f64 / r.ping_count as f64))
+            .collect(),
+    );
+    family(
+        "chain_ping_block_number",
+        "Block height the endpoint last reported.",
+        results.iter().filter_map(|r| r.block_height.map(|height| (prometheus_labels(r, None), height as f64))).collect(),
+    );
+
+    lines.join("\n")
+}
+
+/// `endpoint="..."` and friends, escaped for the exposition format. Tags named like a built-in label are left out.
+fn prometheus_labels(result: &PingResult, stat: Option<&str>) -> String {
+    let mut labels = vec![("endpoint", result.endpoint.as_str())];
+    if let Some(label) = &result.label {
+        labels.push(("label", label));
+    }
+    labels.extend(
+        result
+            .tags
+            .iter()
+            .filter(|(key, _)| !["endpoint", "label", "stat"].contains(&key.as_str()))
+            .map(|(key, value)| (key.as_str(), value.as_str())),
+    );
+    if let Some(stat) = stat {
+        labels.push(("stat", stat));
+    }
+    labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, prometheus_escape(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Backslashes, double quotes and newlines are the only characters label values escape
+fn prometheus_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Escapes text for use in XML attributes and element content
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `"0x10"` becomes `0x10`; results that aren't JSON strings are kept as they are
+fn unquote_json_string(raw: &str) -> String {
+    serde_json::from_str::<String>(raw).unwrap_or_else(|_| raw.to_string())
+}
+
+/// The endpoint's label if it was given one, otherwise its URL
+fn display_name(result: &PingResult) -> &str {
+    result.label.as_deref().unwrap_or(&result.endpoint)
+}
+
+fn status_label(status: PingStatus) -> &'static str {
+    match status {
+        PingStatus::Success => "SUCCESS",
+        PingStatus::PartialSuccess => "PARTIAL",
+        PingStatus::Failure => "FAILURE",
+    }
+}
+
+/// Quotes a CSV field when it contains a delimiter, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// --- TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("https://eth.llamarpc.com"), "https://eth.llamarpc.com");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_block_cell_decimal_unless_raw() {
+        let result = PingResult { result: Some("\"0x10\"".to_string()), block_height: Some(16), ..PingResult::default() };
+        let mut options = RenderOptions::default();
+        assert_eq!(Column::Block.cell(&result, &options).content(), "16");
+        options.raw_block = true;
+        assert_eq!(Column::Block.cell(&result, &options).content(), "\"0x10\"");
+    }
+
+    #[test]
+    fn test_leaderboard_ranks_fastest_first_when_reversed() {
+        let mut results: Vec<PingResult> = [("http://slow", 30.0), ("http://fast", 10.0)]
+            .into_iter()
+            .map(|(endpoint, avg)| PingResult { endpoint: endpoint.to_string(), avg_latency_ms: Some(avg as u128), avg_latency_ms_exact: Some(avg), status: PingStatus::Success, ..PingResult::default() })
+            .collect();
+        let input_order = vec!["http://slow".to_string(), "http://fast".to_string()];
+        crate::sort_results(&mut results, SortBy::Latency, true, &input_order);
+        assert_eq!(results[0].endpoint, "http://slow");
+
+        let rendered = render_leaderboard(&results, &RenderOptions { ascii_only: true, ..RenderOptions::default() });
+        let rows: Vec<&str> = rendered.lines().filter(|line| line.contains("http://")).collect();
+        assert!(rows[0].contains("http://fast"), "{}", rendered);
+        assert!(rows[1].contains("+20ms"), "{}", rendered);
+        assert!(rows.iter().all(|row| !row.contains("+-")), "{}", rendered);
+    }
+
+    #[test]
+    fn test_render_ndjson_one_object_per_line() {
+        let results = vec![
+            PingResult { endpoint: "http://a".to_string(), ..PingResult::default() },
+            PingResult { endpoint: "http://b".to_string(), ..PingResult::default() },
+        ];
+        let rendered = render_ndjson(&results).unwrap();
+        let endpoints: Vec<String> = rendered
+            .lines()
+            .map(|line| serde_json::from_str::<PingResult>(line).unwrap().endpoint)
+            .collect();
+        assert_eq!(endpoints, vec!["http://a", "http://b"]);
+    }
+
+    #[test]
+    fn test_output_prometheus() {
+        let results = vec![
+            PingResult { endpoint: "http://a/?q=\"x\"".to_string(), avg_latency_ms_exact: Some(12.5), success_count: 1, ping_count: 2, block_height: Some(16), ..PingResult::default() },
+            PingResult { endpoint: "http://b".to_string(), ping_count: 2, ..PingResult::default() },
+        ];
+        let rendered = output_prometheus(&results);
+        assert!(rendered.contains(r#"chain_ping_up{endpoint="http://a/?q=\"x\""} 1"#));
+        assert!(rendered.contains(r#"chain_ping_up{endpoint="http://b"} 0"#));
+        assert!(rendered.contains(r#"chain_ping_latency_ms{endpoint="http://a/?q=\"x\"",stat="avg"} 12.5"#));
+        assert!(rendered.contains(r#"chain_ping_success_ratio{endpoint="http://a/?q=\"x\""} 0.5"#));
+        assert!(rendered.contains(r#"chain_ping_block_number{endpoint="http://a/?q=\"x\""} 16"#));
+        assert!(!rendered.contains(r#"chain_ping_latency_ms{endpoint="http://b""#));
+        assert_eq!(rendered.matches("# TYPE chain_ping_up gauge").count(), 1);
+        assert_eq!(prometheus_escape("a\\b\nc"), "a\\\\b\\nc");
+    }
+
+    #[test]
+    fn test_error_cell_breaks_down_mixed_failures() {
+        let options = RenderOptions::default();
+        let mut result = PingResult {
+            error_message: Some("Request timed out".to_string()),
+            error_counts: BTreeMap::from([("timeout".to_string(), 3)]),
+            ..PingResult::default()
+        };
+        assert_eq!(Column::Error.cell(&result, &options).content(), "Request timed out");
+        result.error_counts.insert("http 429".to_string(), 2);
+        assert_eq!(Column::Error.cell(&result, &options).content(), "3x timeout, 2x http 429");
+    }
+
+    #[test]
+    fn test_render_csv_rows() {
+        let results = vec![
+            PingResult { endpoint: "http://a".to_string(), status: PingStatus::Success, avg_latency_ms_exact: Some(12.5), min_latency_ms_exact: Some(10.0), max_latency_ms_exact: Some(15.0), success_count: 2, ping_count: 2, result: Some("\"0x10\"".to_string()), ..PingResult::default() },
+            PingResult { endpoint: "http://b".to_string(), ping_count: 2, error_message: Some("HTTP Error: 403, Forbidden".to_string()), ..PingResult::default() },
+        ];
+        let lines: Vec<String> = render_csv(&results).lines().map(String::from).collect();
+        assert_eq!(lines[1], "http://a,SUCCESS,12.500,10.000,15.000,2,2,0x10,");
+        assert_eq!(lines[2], "http://b,FAILURE,,,,0,2,,\"HTTP Error: 403, Forbidden\"");
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape("https://rpc.example/?a=1&b=<2>"), "https://rpc.example/?a=1&amp;b=&lt;2&gt;");
+        assert_eq!(xml_escape(r#"say "hi" it's"#), "say &quot;hi&quot; it&apos;s");
+    }
+
+    #[test]
+    fn test_format_latency_sub_millisecond() {
+        assert_eq!(format_latency(Some(0), Some(0.4213)), "0.42ms");
+        assert_eq!(format_latency(Some(145), Some(145.25)), "145ms");
+        assert_eq!(format_latency(None, None), "-");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(42), "42 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1_048_576), "3.0 MB");
+    }
+
+    #[test]
+    fn test_format_gwei() {
+        assert_eq!(format_gwei(1_000_000_000), "1.00 gwei");
+        assert_eq!(format_gwei(23_456_000_000), "23.46 gwei");
+        assert_eq!(format_gwei(1_234_567), "0.0012 gwei");
+    }
+
+    #[test]
+    fn test_convergence_points_and_p95() {
+        assert_eq!(convergence_points(3), vec![3]);
+        assert_eq!(convergence_points(40), vec![5, 10, 20, 40]);
+        assert_eq!(convergence_points(50), vec![5, 10, 20, 40, 50]);
+        let samples: Vec<f64> = (1..=20).map(f64::from).collect();
+        assert_eq!(p95(&samples), 19.0);
+        assert_eq!(p95(&[7.0]), 7.0);
+        assert_eq!(percentile(&samples, 0.5), 10.0);
+    }
+
+    #[test]
+    fn test_run_summary_counts() {
+        let results: Vec<PingResult> = [PingStatus::Success, PingStatus::Failure, PingStatus::Success, PingStatus::PartialSuccess]
+            .into_iter()
+            .enumerate()
+            .map(|(i, status)| PingResult {
+                endpoint: format!("http://node{}", i),
+                avg_latency_ms_exact: (status != PingStatus::Failure).then_some(100.0 - i as f64),
+                status,
+                ..PingResult::default()
+            })
+            .collect();
+        let options = RenderOptions { summary: true, ..RenderOptions::default() };
+        let summary = RunSummary::from_results(&results, &options);
+        assert_eq!(
+            summary,
+            RunSummary {
+                total_endpoints: 4,
+                success_count: 2,
+                partial_count: 1,
+                failure_count: 1,
+                fastest_endpoint: Some("http://node3".to_string()),
+                fastest_avg_latency_ms: Some(97.0),
+                slowest_endpoint: Some("http://node0".to_string()),
+                slowest_avg_latency_ms: Some(100.0),
+                duration_ms: None,
+                block_heights: None,
+            }
+        );
+        let text = render_summary(&RunSummary { duration_ms: Some(1500), ..summary }, SummaryFormat::Text).unwrap();
+        assert_eq!(text, "4 endpoints: 2 succeeded, 1 partial, 1 failed. Fastest: http://node3 (97ms), slowest: http://node0 (100ms). Took 1.50s");
+    }
+}
@@ -0,0 +1,313 @@
+
+// --- IMPORTS ---
+
+use std::str::FromStr;
+
+
+// --- DATA STRUCTURES ---
+/// A tiny boolean expression over a fixed set of per-ping variables, used by `--success-if`.
+///
+/// Supports `&&`, `||`, `!`, parentheses, the comparisons `== != < <= > >=`, and number,
+/// `"string"`, `true`, `false` and `null` literals. There are no function calls or assignments,
+/// so evaluating an expression can never do anything but return true or false.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    root: Expr,
+}
+
+/// Values a single ping exposes to a predicate
+#[derive(Debug, Clone, Default)]
+pub struct PredicateVars {
+    /// Round-trip latency in milliseconds
+    pub latency: f64,
+    /// HTTP status code of the response
+    pub status: u16,
+    /// The JSON-RPC `result` (null when absent)
+    pub result: serde_json::Value,
+    /// The JSON-RPC `error.message`, if the endpoint returned an error
+    pub error: Option<String>,
+}
+
+/// The variable names that may appear in an expression
+const VARIABLES: [&str; 5] = ["latency", "status", "result", "error", "block"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+
+// --- PARSING ---
+impl FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(format!("unexpected {:?} in expression", parser.tokens[parser.pos]));
+        }
+        Ok(Predicate { root })
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+        } else if c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&c| c == '"')
+                .ok_or("unterminated string literal")?;
+            tokens.push(Token::Str(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(text.parse().map_err(|_| format!("invalid number '{}'", text))?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let op = ["&&", "||", "==", "!=", "<=", ">="]
+                .into_iter()
+                .find(|op| *op == two)
+                .or_else(|| ["<", ">", "!"].into_iter().find(|op| op.starts_with(c)))
+                .ok_or_else(|| format!("unexpected character '{}'", c))?;
+            tokens.push(Token::Op(op));
+            i += op.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive-descent parser; precedence from loosest to tightest is `||`, `&&`, `!`, comparisons
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn eat_op(&mut self, op: &'static str) -> bool {
+        if self.tokens.get(self.pos) == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_and()?;
+        while self.eat_op("||") {
+            left = Expr::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_not()?;
+        while self.eat_op("&&") {
+            left = Expr::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.eat_op("!") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_compare()
+    }
+
+    fn parse_compare(&mut self) -> Result<Expr, String> {
+        let left = self.parse_primary()?;
+        let op = match self.tokens.get(self.pos) {
+            Some(Token::Op("==")) => CompareOp::Eq,
+            Some(Token::Op("!=")) => CompareOp::Ne,
+            Some(Token::Op("<")) => CompareOp::Lt,
+            Some(Token::Op("<=")) => CompareOp::Le,
+            Some(Token::Op(">")) => CompareOp::Gt,
+            Some(Token::Op(">=")) => CompareOp::Ge,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_primary()?;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Literal(Value::Number(n))),
+            Token::Str(s) => Ok(Expr::Literal(Value::Str(s))),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if VARIABLES.contains(&name.as_str()) => Ok(Expr::Variable(name)),
+                _ => Err(format!("unknown variable '{}'. Available: {}", name, VARIABLES.join(", "))),
+            },
+            Token::LParen => {
+                let inner = self.parse_or()?;
+                if self.tokens.get(self.pos) != Some(&Token::RParen) {
+                    return Err("missing closing parenthesis".to_string());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            other => Err(format!("unexpected {:?} in expression", other)),
+        }
+    }
+}
+
+
+// --- EVALUATION ---
+impl Predicate {
+    /// Evaluates the expression for one ping. Comparisons between mismatched types are false.
+    pub fn evaluate(&self, vars: &PredicateVars) -> bool {
+        truthy(&eval(&self.root, vars))
+    }
+}
+
+fn eval(expr: &Expr, vars: &PredicateVars) -> Value {
+    match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Variable(name) => variable(name, vars),
+        Expr::Not(inner) => Value::Bool(!truthy(&eval(inner, vars))),
+        Expr::And(a, b) => Value::Bool(truthy(&eval(a, vars)) && truthy(&eval(b, vars))),
+        Expr::Or(a, b) => Value::Bool(truthy(&eval(a, vars)) || truthy(&eval(b, vars))),
+        Expr::Compare(a, op, b) => Value::Bool(compare(&eval(a, vars), *op, &eval(b, vars))),
+    }
+}
+
+fn variable(name: &str, vars: &PredicateVars) -> Value {
+    match name {
+        "latency" => Value::Number(vars.latency),
+        "status" => Value::Number(vars.status as f64),
+        "result" => match &vars.result {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => n.as_f64().map(Value::Number).unwrap_or(Value::Null),
+            serde_json::Value::String(s) => Value::Str(s.clone()),
+            other => Value::Str(other.to_string()),
+        },
+        "error" => vars.error.clone().map(Value::Str).unwrap_or(Value::Null),
+        "block" => vars
+            .result
+            .as_str()
+            .and_then(crate::decode_block_number)
+            .map(|height| Value::Number(height as f64))
+            .unwrap_or(Value::Null),
+        _ => Value::Null,
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+fn compare(a: &Value, op: CompareOp, b: &Value) -> bool {
+    match op {
+        CompareOp::Eq => a == b,
+        CompareOp::Ne => a != b,
+        _ => match (a, b) {
+            (Value::Number(a), Value::Number(b)) => match op {
+                CompareOp::Lt => a < b,
+                CompareOp::Le => a <= b,
+                CompareOp::Gt => a > b,
+                _ => a >= b,
+            },
+            _ => false,
+        },
+    }
+}
+
+// --- TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(latency: f64, result: serde_json::Value) -> PredicateVars {
+        PredicateVars { latency, status: 200, result, error: None }
+    }
+
+    #[test]
+    fn test_predicate_evaluation() {
+        let predicate: Predicate = "result != null && latency < 500".parse().unwrap();
+        assert!(predicate.evaluate(&vars(120.0, serde_json::json!("0x10"))));
+        assert!(!predicate.evaluate(&vars(800.0, serde_json::json!("0x10"))));
+        assert!(!predicate.evaluate(&vars(120.0, serde_json::Value::Null)));
+    }
+
+    #[test]
+    fn test_predicate_precedence_and_block() {
+        let predicate: Predicate = "!(error != null) && (block >= 16 || status == 204)".parse().unwrap();
+        assert!(predicate.evaluate(&vars(1.0, serde_json::json!("0x10"))));
+        assert!(!predicate.evaluate(&vars(1.0, serde_json::json!("0x0f"))));
+    }
+
+    #[test]
+    fn test_predicate_parse_errors() {
+        assert!("latency <".parse::<Predicate>().is_err());
+        assert!("latency < 500 &&".parse::<Predicate>().is_err());
+        assert!("gas > 1".parse::<Predicate>().unwrap_err().contains("unknown variable 'gas'"));
+        assert!("(latency < 1".parse::<Predicate>().is_err());
+        assert!("latency = 1".parse::<Predicate>().is_err());
+    }
+}
@@ -0,0 +1,246 @@
+
+// --- IMPORTS ---
+
+use crate::{
+    apply_simulated_loss, attempt_completed, duration_ms, finish, interpret_response, pace, retry_backoff,
+    ErrorKind, PingAttemptResult, PingConfig, PingError, PingEvent, PingResult, Tally,
+};
+use futures::{SinkExt, StreamExt};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, protocol::WebSocketConfig, Message};
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Handed to `success_if` as the status of WebSocket pings, which have no per-message HTTP status
+const WEBSOCKET_STATUS: u16 = 101;
+
+
+// --- PINGING ---
+pub(crate) fn is_websocket_url(url: &str) -> bool {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("ws" | "wss"))
+}
+
+/// Opens one WebSocket connection, timed on its own as `connect_ms`, then sends `config.count`
+/// JSON-RPC requests over it. Each ping's latency is from sending the request to its answer arriving.
+pub(crate) async fn ping_endpoint(url: &str, config: &PingConfig) -> PingResult {
+    config.emit(|| PingEvent::EndpointStarted { endpoint: url.to_string() });
+
+    let connect_start = Instant::now();
+    let mut socket = match config.unless_cancelled(connect(url, config)).await {
+        Some(Ok(socket)) => socket,
+        Some(Err((kind, message))) => {
+            // Like an HTTP client that can't be built, a failed connection fails every ping
+            let tally = Tally::failed_before_sending(kind, message, config.count);
+            return finish(config, tally.into_result(url, config.count, config.rounding));
+        }
+        None => return finish(config, Tally::default().into_run_result(url, config.count, config)),
+    };
+    let connect_time = connect_start.elapsed();
+
+    for warmup in 0..config.warmup {
+        if config.unless_cancelled(ping_once(&mut socket, config.request_id(config.count + warmup), config)).await.is_none() {
+            break;
+        }
+    }
+
+    let mut tally = Tally::start();
+    let mut last_start = None;
+    for index in 0..config.count {
+        if config.unless_cancelled(pace(config, &mut last_start)).await.is_none() {
+            break;
+        }
+        let sent_at = SystemTime::now();
+        let Some(attempt) = config.unless_cancelled(ping_attempt(&mut socket, url, config, index)).await else {
+            break;
+        };
+        tally.record(&attempt, sent_at, Instant::now());
+        config.emit(|| attempt_completed(url, &tally));
+    }
+    let _ = socket.close(None).await;
+
+    let mut result = tally.into_run_result(url, config.count, config);
+    result.connect_ms = Some(duration_ms(&connect_time));
+    finish(config, result)
+}
+
+/// Fails with the kind of error alongside the message: a request that can't be built counts like
+/// an HTTP client that can't be, anything after that as a failed or timed out connection
+async fn connect(url: &str, config: &PingConfig) -> Result<Socket, (ErrorKind, String)> {
+    let invalid = |message: String| (ErrorKind::ClientBuild, message);
+    let mut request = url.into_client_request().map_err(|e| invalid(format!("Invalid WebSocket URL: {}", e)))?;
+    let user_agent = config.user_agent.parse().map_err(|_| invalid(format!("Invalid user agent '{}'", config.user_agent)))?;
+    request.headers_mut().insert("User-Agent", user_agent);
+    if let Some(origin) = &config.origin {
+        let value = origin.parse().map_err(|_| invalid(format!("Invalid origin '{}'", origin)))?;
+        request.headers_mut().insert("Origin", value);
+    }
+    for (name, value) in &config.headers {
+        let name = tungstenite::http::HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid(format!("Invalid header name '{}'", name)))?;
+        let value = value.parse().map_err(|_| invalid(format!("Invalid value for header '{}'", name)))?;
+        request.headers_mut().insert(name, value);
+    }
+    let ws_config = WebSocketConfig {
+        max_message_size: config.max_response_bytes.map(|limit| limit as usize),
+        ..WebSocketConfig::default()
+    };
+
+    // `None` leaves wss:// to the default connector, which verifies certificates
+    let connector = if config.accept_invalid_certs {
+        let tls = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| invalid(format!("WebSocket connect failed: {}", e)))?;
+        Some(Connector::NativeTls(tls))
+    } else {
+        None
+    };
+
+    let connecting = tokio_tungstenite::connect_async_tls_with_config(request, Some(ws_config), false, connector);
+    match tokio::time::timeout(config.connect_timeout(), connecting).await {
+        Ok(Ok((socket, _))) => Ok(socket),
+        Ok(Err(e)) => {
+            // A server that refuses the upgrade answers with a plain HTTP status
+            let kind = match &e {
+                tungstenite::Error::Http(response) => ErrorKind::HttpStatus(response.status().as_u16()),
+                _ => ErrorKind::ConnectionFailed,
+            };
+            Err((kind, format!("WebSocket connect failed: {}", e)))
+        }
+        Err(_) => Err((ErrorKind::ConnectTimeout, "WebSocket connect timed out".to_string())),
+    }
+}
+
+/// One counted ping over the socket, with `config.retries` like HTTP pings get
+async fn ping_attempt(socket: &mut Socket, url: &str, config: &PingConfig, index: usize) -> PingAttemptResult {
+    let mut attempt = ping_once(socket, config.request_id(index), config).await;
+    // Failures of the connection are retried; a JSON-RPC error is the server's deliberate answer,
+    // and a closed socket can only fail again
+    for retry in 0..config.retries {
+        if !matches!(attempt, Err(PingError::WebSocket(_) | PingError::WebSocketTimeout)) {
+            break;
+        }
+        tokio::time::sleep(retry_backoff(config.retry_backoff, retry)).await;
+        // Each try has an id of its own, past the counted and warm-up pings', so a late answer to
+        // the one that timed out isn't taken for this one's
+        let retry_id = config.request_id(config.count + config.warmup + index * config.retries + retry);
+        attempt = ping_once(socket, retry_id, config).await;
+    }
+    apply_simulated_loss(config, url, index, attempt)
+}
+
+async fn ping_once(socket: &mut Socket, id: u64, config: &PingConfig) -> PingAttemptResult {
+    let request_payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": config.method,
+        "params": config.params,
+        "id": id,
+    });
+
+    let start = Instant::now();
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let body = tokio::time::timeout(timeout, async {
+        socket.send(Message::Text(request_payload.to_string())).await.map_err(|e| ws_error(e, config))?;
+        read_answer(socket, id, config).await
+    })
+    .await
+    .map_err(|_| PingError::WebSocketTimeout)??;
+    let latency = start.elapsed();
+
+    interpret_response(body, latency, WEBSOCKET_STATUS, None, &[], config)
+}
+
+/// Reads messages until the one answering request `id`, skipping late answers to earlier pings
+async fn read_answer(socket: &mut Socket, id: u64, config: &PingConfig) -> Result<Vec<u8>, PingError> {
+    while let Some(message) = socket.next().await {
+        let body = match message.map_err(|e| ws_error(e, config))? {
+            Message::Text(text) => text.into_bytes(),
+            Message::Binary(bytes) => bytes,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let answered = serde_json::from_slice::<serde_json::Value>(&body)
+            .map(|json| json.get("id").and_then(|v| v.as_u64()) == Some(id))
+            .unwrap_or(true);
+        if answered {
+            return Ok(body);
+        }
+    }
+    Err(PingError::WebSocketClosed)
+}
+
+fn ws_error(e: tungstenite::Error, config: &PingConfig) -> PingError {
+    match (e, config.max_response_bytes) {
+        (tungstenite::Error::Capacity(_), Some(limit)) => PingError::ResponseTooLarge(limit),
+        (tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed, _) => PingError::WebSocketClosed,
+        (e, _) => PingError::WebSocket(e.to_string()),
+    }
+}
+
+// --- TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PingStatus;
+
+    #[test]
+    fn test_is_websocket_url() {
+        assert!(is_websocket_url("wss://mainnet.example/ws"));
+        assert!(is_websocket_url("WS://localhost:8546"));
+        assert!(!is_websocket_url("https://mainnet.example"));
+        assert!(!is_websocket_url("localhost:8546"));
+    }
+
+    #[tokio::test]
+    async fn test_ping_endpoint_over_websocket() {
+        // A tiny JSON-RPC server that answers every request with block 0x10
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let answer = serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x10" });
+                socket.send(Message::Text(answer.to_string())).await.unwrap();
+            }
+        });
+
+        let config = PingConfig { count: 3, timeout_secs: 2, ..PingConfig::default() };
+        let result = ping_endpoint(&url, &config).await;
+        assert_eq!(result.status, PingStatus::Success);
+        assert_eq!(result.success_count, 3);
+        assert_eq!(result.result.as_deref(), Some("\"0x10\""));
+        assert!(result.connect_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_retry_ignores_late_answer_to_timed_out_try() {
+        // Leaves the first request unanswered, then answers it late, right before the retry's
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut first_id = None;
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let Some(late_id) = first_id.take() else {
+                    first_id = Some(request["id"].clone());
+                    continue;
+                };
+                let late = serde_json::json!({ "jsonrpc": "2.0", "id": late_id, "result": "0xdead" });
+                socket.send(Message::Text(late.to_string())).await.unwrap();
+                let answer = serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x10" });
+                socket.send(Message::Text(answer.to_string())).await.unwrap();
+            }
+        });
+
+        let config = PingConfig { count: 1, timeout_secs: 1, retries: 1, retry_backoff: Duration::from_millis(1), ..PingConfig::default() };
+        let result = ping_endpoint(&url, &config).await;
+        assert_eq!(result.status, PingStatus::Success);
+        assert_eq!(result.result.as_deref(), Some("\"0x10\""));
+    }
+}
@@ -27,4 +27,216 @@ fn test_missing_args_fails() {
     cmd.assert()
         .failure() // Should fail
         .stderr(predicate::str::contains("Error: At least one endpoint"));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_malformed_tag_fails() {
+    // Verify that a --tag without '=' is rejected before any request is sent
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--tag", "env", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected key=value"));
+}
+
+#[test]
+fn test_unknown_format_fails() {
+    // Verify that an unsupported --format is rejected up front
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--format", "xml", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown format 'xml'"));
+}
+
+#[test]
+fn test_unknown_column_fails() {
+    // Verify that an invalid --columns entry lists the valid column names
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--columns", "endpoint,p99", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Valid columns: endpoint, status"));
+}
+
+#[test]
+fn test_malformed_success_if_fails() {
+    // Verify that a bad --success-if expression is rejected at startup
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--success-if", "latency <", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unexpected end of expression"));
+}
+
+#[test]
+fn test_only_without_matches_fails() {
+    // Verify that an --only filter matching nothing is an error rather than an empty run
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--only", "ankr", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No endpoints match --only ankr"));
+}
+
+#[test]
+fn test_unassignable_local_address_fails() {
+    // Verify that a source address this machine doesn't own is rejected before pinging
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--local-address", "192.0.2.123", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--local-address 192.0.2.123 can't be used on this machine"));
+}
+
+#[test]
+fn test_zero_block_time_fails() {
+    // Verify that --pace-to-block-time rejects a non-positive interval
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--pace-to-block-time", "0", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("must be greater than zero"));
+}
+
+#[test]
+fn test_non_array_params_fail() {
+    // Verify that --params must be a JSON array and is checked before any request is sent
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--method", "eth_getBlockByNumber", "--params", "{\"block\": 1}", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected a JSON array"));
+}
+
+#[test]
+fn test_out_of_range_simulated_loss_fails() {
+    // Verify that --simulate-loss only accepts a percentage
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--simulate-loss", "150", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected a percentage from 0 to 100"));
+}
+
+#[test]
+fn test_missing_endpoints_file_fails() {
+    // Verify that an unreadable --endpoints-file is reported instead of silently ignored
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--endpoints-file", "/nonexistent/endpoints.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read endpoints from '/nonexistent/endpoints.txt'"));
+}
+
+#[test]
+fn test_endpoints_file_skips_comments() {
+    // Verify that comments and blank lines read from stdin don't count as endpoints
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--endpoints-file", "-"])
+        .write_stdin("# staging pool\n\n   \n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("At least one endpoint URL is required"));
+}
+
+#[test]
+fn test_labelled_endpoint_shows_label() {
+    // Verify that label=url pings the URL but shows the label (nothing listens on port 1)
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--pings", "1", "--timeout", "1", "--format", "json", "--fail-on", "never", "west=http://127.0.0.1:1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"label\": \"west\""))
+        .stdout(predicate::str::contains("\"endpoint\": \"http://127.0.0.1:1\""));
+}
+
+#[test]
+fn test_header_without_colon_fails() {
+    // Verify that a malformed --header is rejected before any request is sent
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--header", "Authorization Bearer abc", "http://localhost:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected \"Name: Value\""));
+}
+
+#[test]
+fn test_insecure_warns() {
+    // Verify that skipping certificate verification is never silent
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["-k", "--pings", "1", "http://127.0.0.1:1"])
+        .assert()
+        .stderr(predicate::str::contains("TLS certificate verification is disabled"));
+}
+
+#[test]
+fn test_unwritable_output_fails() {
+    // Verify that failing to create the --output file exits non-zero and says why
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--output", "/nonexistent-dir/results.json", "--pings", "1", "http://127.0.0.1:1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to write output to '/nonexistent-dir/results.json'"));
+}
+
+#[test]
+fn test_failed_endpoint_exits_unhealthy() {
+    // Verify that an endpoint where every ping failed exits with code 4 and is named on stderr
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--pings", "1", "--timeout", "1", "http://127.0.0.1:1"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("1 of 1 endpoint unhealthy: http://127.0.0.1:1"));
+}
+
+#[test]
+fn test_quiet_hides_banner() {
+    // Verify that --quiet drops the "Pinging ..." banner but still prints results and errors
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--quiet", "--pings", "1", "--timeout", "1", "--format", "json", "http://127.0.0.1:1"])
+        .assert()
+        .stdout(predicate::str::contains("\"endpoint\": \"http://127.0.0.1:1\""))
+        .stderr(predicate::str::contains("Pinging").not())
+        .stderr(predicate::str::contains("Error: 1 of 1 endpoint unhealthy"));
+}
+
+#[test]
+fn test_block_distribution_in_json() {
+    // Verify that --block-distribution brings the summary's block_heights into JSON output without --summary
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--block-distribution", "--format", "json", "--pings", "1", "--timeout", "1", "--fail-on", "never", "http://127.0.0.1:1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"block_heights\": []"));
+}
+
+#[test]
+fn test_unknown_sort_key() {
+    // Verify that an unknown --sort-by key is rejected before anything is pinged
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--sort-by", "speed", "http://127.0.0.1:1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown sort key 'speed'"));
+}
+
+#[test]
+fn test_connect_timeout_longer_than_timeout() {
+    // Verify that a connect timeout that could never be reached within --timeout is rejected
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--connect-timeout", "5", "--timeout", "2", "http://127.0.0.1:1"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("--connect-timeout (5s) can't be longer than --timeout (2s)"));
+}
+
+#[test]
+fn test_invalid_user_agent() {
+    // Verify that a User-Agent that can't be sent as a header value is rejected up front
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("chain-ping");
+    cmd.args(["--user-agent", "bad\u{1}agent", "http://127.0.0.1:1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid user agent"));
+}